@@ -0,0 +1,46 @@
+//! Benchmarks counting many medium sized files. Run with and without the
+//! `parallel` feature to compare sequential and parallel processing:
+//!
+//! cargo bench --bench multiple_files
+//! cargo bench --bench multiple_files --features parallel
+
+use std::fs;
+use std::path::PathBuf;
+
+use ccwc::{run_structured, Cli};
+use clap::Parser;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const FILE_COUNT: usize = 100;
+const LINES_PER_FILE: usize = 20_000;
+
+/// Writes the benchmark input files to a temporary directory and returns
+/// their paths
+fn write_files() -> Vec<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("ccwc-bench-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let contents = "The quick brown fox jumps over the lazy dog. こんにちは\n".repeat(LINES_PER_FILE);
+    (0..FILE_COUNT)
+        .map(|i| {
+            let path = dir.join(format!("{i}.txt"));
+            fs::write(&path, &contents).unwrap();
+            path
+        })
+        .collect()
+}
+
+fn bench_multiple_files(c: &mut Criterion) {
+    let files = write_files();
+    let mut args = vec!["ccwc".to_owned(), "-lwm".to_owned()];
+    args.extend(files.iter().map(|path| path.to_str().unwrap().to_owned()));
+
+    c.bench_function("count 100 files", |b| {
+        b.iter(|| run_structured(Cli::parse_from(&args)).unwrap())
+    });
+
+    fs::remove_dir_all(files[0].parent().unwrap()).unwrap();
+}
+
+criterion_group!(benches, bench_multiple_files);
+criterion_main!(benches);
@@ -1,73 +1,1239 @@
-/// Library crate for ccwc, a wc clone built in Rust.
+//! Library crate for ccwc, a wc clone built in Rust.
+//!
+//! The types and functions needed to count inputs can be imported together
+//! from the [`prelude`]:
+//!
+//! ```
+//! use ccwc::prelude::*;
+//!
+//! let result = Counter::new(CountConfig::for_words()).count("one two three\n").result();
+//! assert_eq!(result.words, Some(3));
+//!
+//! let results = run_structured(Cli::parse_from(["ccwc", "-l", "test.txt"]))?;
+//! assert_eq!(results[0].filename.as_deref(), Some("test.txt"));
+//! # Ok::<(), CcwcError>(())
+//! ```
 
-use std::io::Read;
-use std::{fs, io};
+use std::io::{IsTerminal, Read, Write};
+use std::path::Path;
+#[cfg(feature = "watch")]
+use std::sync::mpsc;
+use std::{fmt, fs, io};
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::iter::Sum;
+use std::num::NonZeroUsize;
+use std::ops::Add;
+use std::string::FromUtf8Error;
+use std::time::{Duration, Instant};
 
-use::clap::Parser;
+use chrono::{DateTime, SecondsFormat, Utc};
+use::clap::{ArgAction, Parser, ValueEnum};
+use clap_complete::Shell;
+use flate2::read::GzDecoder;
+use glob::Pattern;
+use unicode_casefold::UnicodeCaseFold;
+use unicode_segmentation::UnicodeSegmentation;
+use walkdir::WalkDir;
+#[cfg(feature = "watch")]
+use notify::{EventKind, RecursiveMode, Watcher};
+use termcolor::{Buffer, Color, ColorSpec, WriteColor};
+#[cfg(feature = "regex")]
+use regex::Regex;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+#[cfg(feature = "tokio")]
+use tokio::io::AsyncReadExt;
+
+/// Re-exports the items that most users of the library need: the Cli and
+/// CountConfig that say what to count, the Counter and functions that count,
+/// the CountResult they produce, the error type, and clap's `Parser` trait so
+/// that the Cli can be parsed. The single purpose counting functions, such as
+/// `count_words`, are left out because their short names are likely to clash
+/// with names in the importing crate; import them from the crate root instead.
+pub mod prelude {
+    pub use crate::{
+        count_streaming, format_all, format_csv, run, run_structured, run_with_reader, Average,
+        CcwcError, Cli,
+        CountConfig, CountConfigBuilder, CountResult, Counter, Encoding, FormatOptions, Histogram,
+        CommentStyle, InputFormat, LineStats,
+    };
+    pub use clap::Parser;
+}
+
+/// JavaScript bindings for the single purpose counting functions, for when
+/// the crate is compiled to WebAssembly with the `wasm` feature, e.g. with
+/// `make wasm`. Each takes the string from JavaScript as a `&str` that is
+/// counted in place without being copied again.
+#[cfg(feature = "wasm")]
+pub mod wasm {
+    use wasm_bindgen::prelude::wasm_bindgen;
+
+    /// The number of bytes in the UTF-8 encoding of the input, see
+    /// [`count_bytes`](crate::count_bytes)
+    #[wasm_bindgen]
+    pub fn wc_bytes(input: &str) -> usize {
+        crate::count_bytes(input)
+    }
+
+    /// The number of whitespace separated words in the input, see
+    /// [`count_words`](crate::count_words)
+    #[wasm_bindgen]
+    pub fn wc_words(input: &str) -> usize {
+        crate::count_words(input)
+    }
+
+    /// The number of newlines in the input, see
+    /// [`count_lines`](crate::count_lines)
+    #[wasm_bindgen]
+    pub fn wc_lines(input: &str) -> usize {
+        crate::count_lines(input)
+    }
+
+    /// The number of Unicode code points in the input, see
+    /// [`count_characters`](crate::count_characters)
+    #[wasm_bindgen]
+    pub fn wc_chars(input: &str) -> usize {
+        crate::count_characters(input)
+    }
+}
 
 /// A wc clone built in Rust.
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Default)]
+#[command(disable_help_flag = true, version = VERSION)]
 pub struct Cli {
     /// Count the number of bytes the input. If `-m` is specified then that
     /// option overrides this one.
-    #[arg(short = 'c')]
+    #[arg(short = 'c', long = "bytes")]
     byte_count: bool,
 
     /// Count the number of words in the input. Words are defined as being
-    /// separated by whitespace characters.
-    #[arg(short = 'w')]
+    /// separated by Unicode whitespace characters, which includes non-ASCII
+    /// ones such as U+00A0 NO-BREAK SPACE unlike GNU wc in the C locale.
+    #[arg(short = 'w', long = "words")]
     word_count: bool,
 
-    /// Count the number of lines in the input. Lines are separated by the
-    /// newline character `\n`.
-    #[arg(short = 'l')]
+    /// Count the number of lines in the input. This is the number of newline
+    /// characters `\n`, so a final line without a trailing newline is not
+    /// counted unless `--logical-lines` is given.
+    #[arg(short = 'l', long = "lines")]
     line_count: bool,
 
-    /// Count the number of characters in the string. If the current locale does
-    /// not support mutlibyte characters then this will be the same as the byte
-    /// count. Use of `-m` will override any usage of `-c`. Note that this is
-    /// different from `wc` where the last of the two flags specified will be
-    /// used. Here `-m` is always preferred to `-c`.
-    #[arg(short = 'm')]
+    /// Count logical lines with `-l` rather than newline characters, so that
+    /// a final line without a trailing newline is counted too, as Rust's
+    /// `str::lines` does. By default the newlines are counted as POSIX and GNU
+    /// `wc` do.
+    #[arg(long = "logical-lines")]
+    logical_lines: bool,
+
+    /// Count the number of characters in the string. What a character is
+    /// depends on `--locale`: by default it is a Unicode code point, but in the
+    /// `C` locale, which does not support multibyte characters, this will be
+    /// the same as the byte count. Use of `-m` will override any usage of
+    /// `-c`. Note that this is different from `wc` where the last of the two
+    /// flags specified will be used. Here `-m` is always preferred to `-c`.
+    #[arg(short = 'm', long = "chars")]
     char_count: bool,
 
-    /// If provided this should be the name of a file to read in as input. If
-    /// not provided then stdin will be used as the input.
-    filename: Option<String>,
+    /// Count the number of grapheme clusters, which is the number of
+    /// characters that a reader would see, so that an emoji with a skin tone
+    /// modifier or a letter with a combining accent is one character. Printed
+    /// in the character column, and overrides `-m` and `-c`.
+    #[arg(short = 'g', long = "grapheme-count")]
+    grapheme_count: bool,
+
+    /// Print the length of the longest line in the input. The line terminator
+    /// is not included in the length.
+    #[arg(short = 'L', long = "max-line-length")]
+    max_line_length: bool,
+
+    /// Print the length in characters of the longest word in the input. Words
+    /// are split in the same way as for `-w`.
+    #[arg(long = "max-word-length")]
+    max_word_length: bool,
+
+    /// Print the length in bytes of the shortest line, the longest line, and
+    /// the mean length of the lines in the input. Line terminators are not
+    /// included in the lengths.
+    #[arg(long = "line-stats")]
+    line_stats: bool,
+
+    /// Count the number of distinct words in the input. Every distinct word is
+    /// held in memory while counting.
+    #[arg(long = "unique-words")]
+    unique_words: bool,
+
+    /// Count the number of distinct lines in the input. Every distinct line is
+    /// held in memory while counting, so for a file with few repeated lines
+    /// this uses memory proportional to the size of the file.
+    #[arg(long = "unique-lines")]
+    unique_lines: bool,
+
+    /// Count the number of lines in the input that repeat an earlier line
+    /// exactly, so a line that occurs three times counts as two duplicates.
+    /// Uses the same memory as `--unique-lines`, and both are counted in one
+    /// pass over the lines if they are given together.
+    #[arg(long = "duplicate-lines")]
+    duplicate_lines: bool,
+
+    /// Count the number of paragraphs in the input. A paragraph is one or more
+    /// lines that are not blank, separated from other paragraphs by one or
+    /// more blank lines. Lines containing only whitespace are blank.
+    #[arg(short = 'p', long = "paragraph-count")]
+    paragraph_count: bool,
+
+    /// Count the number of lines that are not blank. Lines containing only
+    /// whitespace are blank. Unlike `-l` a final line without a trailing
+    /// newline is counted.
+    #[arg(long = "non-blank-lines")]
+    non_blank: bool,
+
+    /// Count the number of tab characters in the input.
+    #[arg(long = "tab-count")]
+    tab_count: bool,
+
+    /// Count the number of ASCII space characters in the input. Other
+    /// whitespace characters, such as tabs and newlines, are not counted.
+    #[arg(long = "space-count")]
+    space_count: bool,
+
+    /// Count the number of ASCII control characters in the input, bytes
+    /// 0x00 to 0x1F and 0x7F, which may be a sign of a binary or corrupted
+    /// file. Tabs, newlines, and carriage returns are not counted unless
+    /// `--include-common-controls` is given.
+    #[arg(long = "count-control-chars")]
+    control_chars: bool,
+
+    /// Also count tabs, newlines, and carriage returns with
+    /// `--count-control-chars`.
+    #[arg(long = "include-common-controls", requires = "control_chars")]
+    include_common_controls: bool,
+
+    /// Count the number of non-ASCII characters in the input, after it has
+    /// been decoded in the `--encoding`, to check whether it is pure ASCII.
+    #[arg(long = "count-non-ascii")]
+    non_ascii: bool,
+
+    /// Count the bytes of the non-ASCII characters in UTF-8 rather than the
+    /// characters with `--count-non-ascii`.
+    #[arg(long = "non-ascii-bytes", requires = "non_ascii")]
+    non_ascii_bytes: bool,
+
+    /// Count the number of Windows `\r\n` line endings in the input, which
+    /// with `-l` shows how many of the lines end in `\r\n` rather than `\n`.
+    /// They are counted before `--crlf` normalizes them.
+    #[arg(long = "windows-line-endings")]
+    windows_line_endings: bool,
+
+    /// Count the number of lines that match the given regular expression
+    /// anywhere in the line, e.g. `--lines-containing 'TODO|FIXME'`.
+    #[cfg(feature = "regex")]
+    #[arg(long = "lines-containing", value_name = "REGEX", value_parser = Regex::new)]
+    lines_containing: Option<Regex>,
+
+    /// Count the words as the non-overlapping matches of the given regular
+    /// expression rather than as runs of non-whitespace characters, e.g.
+    /// `--word-regex '[a-zA-Z]+'` to only count alphabetic words. Only changes
+    /// what `-w` counts.
+    #[cfg(feature = "regex")]
+    #[arg(
+        long = "word-regex",
+        value_name = "PATTERN",
+        value_parser = Regex::new,
+        conflicts_with = "delimiter",
+    )]
+    word_regex: Option<Regex>,
+
+    /// Count the fields of each line separated by the given character, e.g.
+    /// `--delimiter ,` for CSV, rather than whitespace separated words. The
+    /// fields are printed in the word column, and an empty line has one
+    /// empty field. Only changes what `-w` counts.
+    #[arg(long = "delimiter", value_name = "CHAR")]
+    delimiter: Option<char>,
+
+    /// Count the number of sentences in the input. This is approximate: a
+    /// sentence ends at a word ending in `.`, `!` or `?`, ignoring closing
+    /// quotes and brackets, but common abbreviations such as `Mr.` and `e.g.`
+    /// and initials such as `J.` do not end a sentence unless they end the
+    /// input.
+    #[arg(long = "sentence-count")]
+    sentence_count: bool,
+
+    /// Print the mean length of the words in the input in characters, to two
+    /// decimal places.
+    #[arg(long = "average-word-length")]
+    average_word_length: bool,
+
+    /// Print the mean length of the lines in the input in characters, to two
+    /// decimal places. Line terminators are not included, and unlike `-l` a
+    /// final line without a trailing newline is counted.
+    #[arg(long = "average-line-length")]
+    average_line_length: bool,
+
+    /// Print a histogram of the lengths of the lines in characters after the
+    /// counts, as a bar chart of how many lines are 0-9 characters long, 10-19
+    /// and so on. With more than one input it is of all of them together.
+    #[arg(
+        long = "histogram",
+        visible_alias = "line-length-histogram",
+        conflicts_with_all = ["csv", "frequency", "diff", "zero_terminated"]
+    )]
+    histogram: bool,
+
+    /// The number of ranges of line lengths in the `--histogram`. The last
+    /// one includes every longer line, e.g. `100+` with the default of 11.
+    #[arg(
+        long = "histogram-buckets",
+        value_name = "N",
+        default_value_t = DEFAULT_HISTOGRAM_BUCKETS,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        requires = "histogram"
+    )]
+    histogram_buckets: usize,
+
+    /// Print how many times each word occurs in the inputs instead of the
+    /// counts, as `<count>\t<word>` with the most frequent words first and
+    /// words that occur equally often in alphabetical order.
+    #[arg(long = "freq", conflicts_with = "csv")]
+    frequency: bool,
+
+    /// Only print the N most frequent words with `--freq`.
+    #[arg(long = "top", value_name = "N", requires = "frequency")]
+    top: Option<usize>,
+
+    /// Ignore case when deciding whether words or lines are distinct, so that
+    /// `Word` and `word` are counted once by `--unique-words`. Uses full
+    /// Unicode case folding, so `Straße` and `STRASSE` are the same word. The
+    /// input is copied while it is folded, which doubles the memory used.
+    #[arg(long = "ignore-case")]
+    ignore_case: bool,
+
+    /// Treat Windows `\r\n` line endings as a single `\n` so that the `\r`
+    /// is not included in the byte, character, or line length counts.
+    #[arg(long = "crlf")]
+    crlf: bool,
+
+    /// Remove a UTF-8 byte order mark, `EF BB BF`, from the start of each
+    /// input before counting it, so that it is not counted as 3 bytes and a
+    /// character. Only applies with the UTF-8 encoding.
+    #[arg(long = "strip-bom")]
+    strip_bom: bool,
+
+    /// Remove the whitespace from the start and end of each line before
+    /// counting, for inputs with padded lines such as fixed-width logs. This
+    /// changes the character count and the line lengths, but the byte count is
+    /// always that of the input as it is.
+    #[arg(long = "trim-whitespace")]
+    trim_whitespace: bool,
+
+    /// Remove the comments in the given style before counting, to count the
+    /// lines and words of code. Lines that only held comments are removed
+    /// too, but the byte count is always that of the input as it is. As it
+    /// looks like an option, the SQL style has to be given as
+    /// `--comment-strip=--` or `--comment-strip sql`.
+    #[arg(long = "comment-strip", value_enum, value_name = "STYLE")]
+    comment_strip: Option<CommentStyle>,
+
+    /// Do not print the filenames, or the `total` label, after the counts
+    /// even when files are given as arguments.
+    #[arg(long = "no-filename")]
+    no_filename: bool,
+
+    /// Print only the counts, separated by single spaces without any padding,
+    /// and no filenames, e.g. `3 6 50`, for use in shell arithmetic such as
+    /// `$(ccwc --number -l file)`.
+    #[arg(
+        long = "number",
+        visible_alias = "count-only",
+        conflicts_with_all = ["csv", "frequency", "diff", "header", "tabulate", "column_separator"]
+    )]
+    number: bool,
+
+    /// The name to print after the counts when reading stdin, e.g.
+    /// `--stdin-label '<stdin>'`. By default no name is printed, or `-` with
+    /// `--posix`.
+    #[arg(long = "stdin-label", value_name = "LABEL")]
+    stdin_label: Option<String>,
+
+    /// Follow POSIX conventions where they differ from the defaults, which
+    /// labels stdin as `-`.
+    #[arg(long = "posix")]
+    posix: bool,
+
+    /// When to print a line with the totals of the counts across all of the
+    /// inputs.
+    #[arg(long = "total", value_enum, value_name = "WHEN", default_value_t = TotalMode::Auto)]
+    total: TotalMode,
+
+    /// Sort the counts of each input by this field, smallest first, before
+    /// printing them. The totals are always printed last.
+    #[arg(long = "sort-by", value_enum, value_name = "FIELD")]
+    sort_by: Option<SortField>,
+
+    /// Reverse the order given by `--sort-by`, e.g. to print the largest file
+    /// first.
+    #[arg(long = "sort-reverse", requires = "sort_by")]
+    sort_reverse: bool,
+
+    /// Leave out the counts of inputs with fewer lines than this, though
+    /// they are still included in the totals. Only applies if lines are
+    /// counted.
+    #[arg(long = "min-lines", value_name = "N")]
+    min_lines: Option<usize>,
+
+    /// Leave out the counts of inputs with more lines than this, though they
+    /// are still included in the totals. Only applies if lines are counted.
+    #[arg(long = "max-lines", value_name = "N")]
+    max_lines: Option<usize>,
+
+    /// Leave out the counts of inputs with fewer words than this, though
+    /// they are still included in the totals. Only applies if words are
+    /// counted.
+    #[arg(long = "min-words", value_name = "N")]
+    min_words: Option<usize>,
+
+    /// Leave out the counts of inputs with more words than this, though they
+    /// are still included in the totals. Only applies if words are counted.
+    #[arg(long = "max-words", value_name = "N")]
+    max_words: Option<usize>,
+
+    /// Leave out the counts of inputs with fewer bytes than this, though
+    /// they are still included in the totals. Only applies if bytes are
+    /// counted.
+    #[arg(long = "min-bytes", value_name = "N")]
+    min_bytes: Option<usize>,
+
+    /// Leave out the counts of inputs with more bytes than this, though they
+    /// are still included in the totals. Only applies if bytes are counted.
+    #[arg(long = "max-bytes", value_name = "N")]
+    max_bytes: Option<usize>,
+
+    /// Fail with an error if the count of any input is less than N, e.g. in
+    /// CI. Exactly one count must be given, such as `-l`.
+    #[arg(long = "assert-min", value_name = "N")]
+    assert_min: Option<usize>,
+
+    /// Fail with an error if the count of any input is more than N, e.g.
+    /// `ccwc -l --assert-max 1000 big_file.txt` in CI. Exactly one count must
+    /// be given, such as `-l`.
+    #[arg(long = "assert-max", value_name = "N")]
+    assert_max: Option<usize>,
+
+    /// When to color the counts and filenames: `auto` only colors them if
+    /// stdout is a terminal.
+    #[arg(long = "color", value_enum, value_name = "WHEN", default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+
+    /// Never color the output, the same as `--color never`.
+    #[arg(long = "no-color", conflicts_with = "color")]
+    no_color: bool,
+
+    /// Keep running and print the counts again each time the input file
+    /// changes, overwriting the previous counts. The file may be deleted and
+    /// recreated. Only a single named file can be watched, not stdin. With
+    /// `--json` or `--csv` the counts are printed on a new line each time.
+    #[cfg(feature = "watch")]
+    #[arg(short = 'W', long = "watch", conflicts_with_all = ["quiet", "check"])]
+    watch: bool,
+
+    /// Print nothing, and exit with a status of 0 if the count is more than
+    /// zero across all of the inputs or 1 if it is zero, e.g. `ccwc -q -l file`
+    /// to check whether a file has any lines. Exactly one count must be given.
+    /// The status is 2 if there is an error, such as a file not existing.
+    #[arg(
+        short = 'q',
+        long = "quiet",
+        conflicts_with_all = ["csv", "frequency", "diff", "check", "histogram"]
+    )]
+    quiet: bool,
+
+    /// Print byte and character counts in human readable powers of 1024,
+    /// e.g. `1.5KiB` or `2.0MiB`, rather than as exact numbers.
+    #[arg(short = 'h', long = "human-readable", conflicts_with = "si")]
+    human_readable: bool,
+
+    /// Print byte and character counts in human readable powers of 1000,
+    /// e.g. `1.5kB` or `2.0MB`, rather than as exact numbers.
+    #[arg(long = "si")]
+    si: bool,
+
+    /// Always print the line, word, byte, and character counts, in that order,
+    /// printing `0` for any of them that were not requested, so that the
+    /// number of columns does not depend on which counts were requested.
+    #[arg(long = "include-zero")]
+    include_zero: bool,
+
+    /// Print the given separator between the counts and the filename rather
+    /// than lining them up in columns, e.g. `--column-separator ,`. The
+    /// default of an empty separator keeps the columns.
+    #[arg(long = "column-separator", value_name = "SEP", default_value = "")]
+    column_separator: String,
+
+    /// Separate the counts and the filename with tabs rather than lining them
+    /// up in columns, so that tab stops or `column -t` can align them however
+    /// long the filenames are. The same as `--column-separator $'\t'`.
+    #[arg(long = "tabulate", conflicts_with = "column_separator")]
+    tabulate: bool,
+
+    /// Print a row naming the columns, such as `lines words bytes filename`,
+    /// before the counts.
+    #[arg(long = "header", overrides_with = "omit_header")]
+    header: bool,
+
+    /// Do not print a row naming the columns. This is the default, but can be
+    /// given to override an earlier `--header`.
+    #[arg(long = "omit-header", overrides_with = "header")]
+    omit_header: bool,
+
+    /// Print the script that sets up tab completion of ccwc's options for the
+    /// given shell, and exit.
+    #[arg(long = "generate-completions", value_name = "SHELL")]
+    generate_completions: Option<Shell>,
+
+    /// Print the configuration of the counts that the other options resolve
+    /// to, such as which of -c and -m wins, to stderr and exit without reading
+    /// any input.
+    #[arg(long = "print-config", visible_alias = "dry-run")]
+    print_config: bool,
+
+    /// Print help. Note that `-h` is `--human-readable` rather than help.
+    #[arg(long = "help", action = ArgAction::Help)]
+    help: Option<bool>,
+
+    /// Read the names of the input files from the given file, where they are
+    /// separated by NUL characters. If the file is `-` then the names are read
+    /// from stdin. Cannot be combined with filenames given on the command line.
+    #[arg(long = "files0-from", value_name = "FILE")]
+    files0_from: Option<String>,
+
+    /// Read the names of the input files from stdin, where they are separated
+    /// by NUL characters, e.g. `find . -print0 | ccwc --from-nul-stdin`. The
+    /// same as `--files0-from -`.
+    #[arg(
+        long = "from-nul-stdin",
+        conflicts_with_all = ["files0_from", "from_stdin_filenames", "filenames"],
+    )]
+    from_nul_stdin: bool,
+
+    /// Read the names of the input files from stdin, one per line, rather
+    /// than counting stdin itself, e.g. `git ls-files | ccwc
+    /// --from-stdin-filenames`. Empty lines are skipped.
+    #[arg(long = "from-stdin-filenames", conflicts_with_all = ["files0_from", "filenames"])]
+    from_stdin_filenames: bool,
+
+    /// Count every regular file in any directories given as inputs, and in
+    /// their subdirectories, as well as the files that are given directly.
+    #[arg(short = 'r', long = "recursive")]
+    recursive: bool,
+
+    /// Print the progress of reading each large file to stderr even if stderr
+    /// is not a terminal. By default progress is only printed to a terminal.
+    #[arg(long = "progress", conflicts_with = "no_progress")]
+    progress: bool,
+
+    /// Never print the progress of reading large files.
+    #[arg(long = "no-progress")]
+    no_progress: bool,
+
+    /// The size in bytes from which the progress of reading a file is
+    /// printed. Defaults to 64MiB.
+    #[arg(long = "progress-threshold", value_name = "BYTES")]
+    progress_threshold: Option<u64>,
+
+    /// Only count the first N bytes of each input, e.g. to quickly estimate
+    /// the counts of a large file. N may end in `K`, `M`, or `G` for
+    /// kibibytes, mebibytes, or gibibytes. The byte count is always exactly N
+    /// for a larger input, but if the limit falls in the middle of a
+    /// multi-byte character then that character is left out of the other
+    /// counts, so `-m` may undercount the final character.
+    #[arg(long = "limit", value_name = "N", value_parser = parse_size)]
+    limit: Option<u64>,
+
+    /// Skip any input files that look like they are binary rather than text,
+    /// because there is a NUL byte in their first 8KiB. A message is printed
+    /// to stderr for each skipped file and it is left out of the total.
+    #[arg(long = "skip-binary")]
+    skip_binary: bool,
+
+    /// With `-r` only count the files in directories whose names match the
+    /// glob, e.g. `*.rs`. Can be given more than once to match any of them.
+    #[arg(long = "include", value_name = "GLOB", requires = "recursive")]
+    include: Vec<String>,
+
+    /// With `-r` skip the files in directories whose names match the glob.
+    /// Can be given more than once to skip any of them.
+    #[arg(long = "exclude", value_name = "GLOB", requires = "recursive")]
+    exclude: Vec<String>,
+
+    /// With `-r` do not follow symbolic links while walking directories, so
+    /// that links to files are not counted and links to directories are not
+    /// walked.
+    #[arg(long = "no-follow-symlinks", requires = "recursive")]
+    no_follow_symlinks: bool,
+
+    /// With `-r` stop after counting N files, including those given directly,
+    /// and print a warning to stderr, as a guard against walking far more of
+    /// the filesystem than intended. By default there is no limit.
+    #[arg(long = "max-files", value_name = "N", requires = "recursive")]
+    max_files: Option<usize>,
+
+    /// Files of at least this many bytes are memory mapped rather than being
+    /// read into memory. Only UTF-8 files that are not compressed are mapped.
+    /// Defaults to 64MiB.
+    #[cfg(feature = "mmap")]
+    #[arg(long = "mmap-threshold", value_name = "BYTES")]
+    mmap_threshold: Option<u64>,
+
+    /// Lines in the input are separated by NUL characters rather than newline
+    /// characters, as produced by e.g. `find -print0`. This changes what is
+    /// counted by `-l`.
+    #[arg(short = 'z', long = "null-data")]
+    null_data: bool,
+
+    /// End each line of the output, including the totals, with a NUL
+    /// character rather than a newline, for tools such as `xargs -0`.
+    /// Unlike newlines the last line also ends with a NUL character.
+    #[arg(
+        short = 'Z',
+        long = "zero-terminated",
+        visible_alias = "print0",
+        conflicts_with_all = ["csv", "frequency", "diff"]
+    )]
+    zero_terminated: bool,
+
+    /// The character encoding of the input. Characters are counted after the
+    /// input has been decoded, while bytes are counted in the given encoding.
+    /// It is an error for the input not to be valid in the encoding.
+    #[arg(long = "encoding", value_enum, value_name = "ENCODING", default_value_t = Encoding::Utf8)]
+    encoding: Encoding,
+
+    /// Guess the character encoding of each input instead of using
+    /// `--encoding`, print it to stderr, and decode the input from it before
+    /// counting. Bytes are still counted as they are in the input.
+    #[arg(long = "encoding-detect", conflicts_with = "encoding")]
+    encoding_detect: bool,
+
+    /// The format of the input. With `json` only the string values in the
+    /// JSON document are counted, and with `csv` only the values of the
+    /// fields, so that e.g. the words in the text are counted rather than
+    /// the syntax around it. Every count, including -c, is of the extracted
+    /// text.
+    #[arg(
+        long = "input-format",
+        value_enum,
+        value_name = "FORMAT",
+        default_value_t = InputFormat::Text
+    )]
+    input_format: InputFormat,
+
+    /// The locale that decides what a character is for `-m`: `C` counts
+    /// bytes, `UTF-8` counts Unicode code points, and `unicode` counts
+    /// grapheme clusters, so that e.g. an accented letter made of a letter and
+    /// a combining accent is one character.
+    #[arg(
+        long = "locale",
+        value_enum,
+        value_name = "LOCALE",
+        default_value_t = Locale::Utf8,
+        ignore_case = true,
+    )]
+    locale: Locale,
+
+    /// Print the counts as JSON rather than in columns. A single input is
+    /// printed as an object and several inputs as an array of objects, with
+    /// `null` for any count that was not requested.
+    #[cfg(feature = "json")]
+    #[arg(
+        long = "json",
+        conflicts_with_all = ["csv", "frequency", "diff", "zero_terminated", "number", "quiet"]
+    )]
+    json: bool,
+
+    /// Print the counts as comma separated values with a header row. Counts
+    /// that were not requested are left empty.
+    #[arg(long = "csv")]
+    csv: bool,
+
+    /// Compare the counts of two files, printing how each count changed from
+    /// the first file, the before, to the second, the after, e.g.
+    /// `lines: 100 -> 150 (+50)`.
+    #[arg(
+        long = "diff",
+        num_args = 2,
+        value_names = ["BEFORE", "AFTER"],
+        conflicts_with_all = ["filenames", "files0_from", "from_stdin_filenames", "csv", "frequency"],
+    )]
+    diff: Option<Vec<String>>,
+
+    /// Read a file of counts previously printed by ccwc with the same flags,
+    /// count each of the files named in it again, and print `<file>: OK` or
+    /// `<file>: FAILED` depending on whether the counts still match. Exits
+    /// with a non-zero exit code if any of them do not. Lines for the total
+    /// are skipped.
+    #[arg(
+        long = "check",
+        value_name = "FILE",
+        conflicts_with_all = ["filenames", "files0_from", "from_stdin_filenames", "csv", "frequency", "diff"],
+    )]
+    check: Option<String>,
+
+    /// Also append a row with the current time and the counts of each input,
+    /// as comma separated values, to the given file. The file is started with
+    /// a header row if it does not exist or is empty.
+    #[arg(long = "append", value_name = "FILE")]
+    append: Option<String>,
+
+    /// Write the output to the given file rather than stdout, replacing the
+    /// file if it exists. As with redirecting stdout, the file is emptied
+    /// before the inputs are read.
+    #[arg(long = "output-file", value_name = "FILE", conflicts_with = "output_append")]
+    output_file: Option<String>,
+
+    /// Append the output to the given file rather than writing it to stdout,
+    /// creating the file if it does not exist.
+    #[arg(long = "output-append", value_name = "FILE")]
+    output_append: Option<String>,
+
+    /// If provided these should be the names of files to read in as input. If
+    /// not provided then stdin will be used as the input. When more than one
+    /// file is given an extra line with the totals is printed, see `--total`.
+    /// Files ending in `.gz` are decompressed and their contents are counted.
+    filenames: Vec<String>,
+}
+
+/// The version printed by `--version`, including the commit that was built and
+/// the date it was built on
+pub const VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (commit ",
+    env!("CCWC_COMMIT_HASH"),
+    ", built ",
+    env!("CCWC_BUILD_DATE"),
+    ")",
+);
+
+/// The size in bytes from which files are memory mapped by default
+#[cfg(feature = "mmap")]
+pub const DEFAULT_MMAP_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// Parses a size in bytes that may end in `K`, `M`, or `G` for powers of
+/// 1024, such as `512`, `64K`, or `1M`
+fn parse_size(size: &str) -> Result<u64, String> {
+    let (number, multiplier) = match size.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&size[..i], 1024),
+        Some((i, 'M' | 'm')) => (&size[..i], 1024 * 1024),
+        Some((i, 'G' | 'g')) => (&size[..i], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| format!("`{size}` is not a size in bytes, e.g. 512, 64K, or 1M"))
+}
+
+/// The number of ranges of line lengths in a histogram by default
+const DEFAULT_HISTOGRAM_BUCKETS: usize = 11;
+
+/// The number of line lengths in each range of a histogram
+const HISTOGRAM_BUCKET_SIZE: usize = 10;
+
+/// The size in bytes from which the progress of reading a file is printed by
+/// default
+const DEFAULT_PROGRESS_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// The options for when to print the progress of reading large files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum ProgressMode {
+    /// Print the progress if stderr is a terminal
+    #[default]
+    Auto,
+    /// Always print the progress
+    Always,
+    /// Never print the progress
+    Never,
+}
+
+/// The options for when to print the line with the totals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum TotalMode {
+    /// Print the totals when there is more than one input
+    #[default]
+    Auto,
+    /// Always print the totals
+    Always,
+    /// Only print the totals and not the counts of each input
+    Only,
+    /// Never print the totals
+    Never,
+}
+
+/// The fields that the counts of each input can be sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SortField {
+    /// The number of lines
+    Lines,
+    /// The number of words
+    Words,
+    /// The number of bytes
+    Bytes,
+    /// The number of characters
+    Chars,
+    /// The filename, in byte order
+    Filename,
+}
+
+impl SortField {
+    /// Sorts the results by this field, keeping the order of any that are
+    /// equal. Counts that were not made sort first.
+    fn sort(self, results: &mut [CountResult]) {
+        match self {
+            SortField::Lines => results.sort_by_key(|result| result.lines),
+            SortField::Words => results.sort_by_key(|result| result.words),
+            SortField::Bytes => results.sort_by_key(|result| result.bytes),
+            SortField::Chars => results.sort_by_key(|result| result.chars),
+            SortField::Filename => results.sort_by_key(|result| result.filename.clone()),
+        }
+    }
+}
+
+/// The options for when to color the output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum ColorMode {
+    /// Color the output if stdout is a terminal
+    #[default]
+    Auto,
+    /// Always color the output
+    Always,
+    /// Never color the output
+    Never,
+}
+
+/// The styles of comments that can be stripped before counting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CommentStyle {
+    /// C++ style line comments starting with `//`
+    #[value(name = "//", alias = "cpp")]
+    DoubleSlash,
+    /// Shell and Python style line comments starting with `#`
+    #[value(name = "#", alias = "shell")]
+    Hash,
+    /// SQL style line comments starting with `--`
+    #[value(name = "--", alias = "sql")]
+    DoubleDash,
+    /// C style block comments between `/*` and `*/`
+    #[value(name = "/* */", aliases = ["/*", "c"])]
+    Block,
+}
+
+impl CommentStyle {
+    /// The characters that quote strings in languages with this style of
+    /// comment, inside which comments are not stripped
+    fn quotes(self) -> &'static [char] {
+        match self {
+            CommentStyle::DoubleSlash | CommentStyle::Block => &['"'],
+            CommentStyle::Hash | CommentStyle::DoubleDash => &['"', '\''],
+        }
+    }
+}
+
+/// The locales that decide what is counted as a character
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Locale {
+    /// Every byte is a character
+    #[value(name = "C")]
+    C,
+    /// Every Unicode code point is a character
+    #[default]
+    #[value(name = "UTF-8")]
+    Utf8,
+    /// Every extended grapheme cluster is a character
+    #[value(name = "unicode")]
+    Unicode,
+}
+
+/// The character encodings that the input can be read in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Encoding {
+    /// UTF-8, which ASCII is a subset of
+    #[default]
+    #[value(name = "utf-8")]
+    Utf8,
+    /// ISO-8859-1, where every byte is a single character
+    #[value(name = "latin-1")]
+    Latin1,
+    /// UTF-16 with the least significant byte first
+    #[value(name = "utf-16le")]
+    Utf16Le,
+    /// UTF-16 with the most significant byte first
+    #[value(name = "utf-16be")]
+    Utf16Be,
+}
+
+impl Encoding {
+    /// Decodes the input into a string. A byte order mark at the start of the
+    /// input is kept as a U+FEFF character so that it is still counted.
+    pub fn decode(self, input: Vec<u8>) -> Result<String, CcwcError> {
+        let encoding = match self {
+            Encoding::Utf8 => return Ok(String::from_utf8(input)?),
+            Encoding::Latin1 => return Ok(input.iter().map(|&b| char::from(b)).collect()),
+            Encoding::Utf16Le => encoding_rs::UTF_16LE,
+            Encoding::Utf16Be => encoding_rs::UTF_16BE,
+        };
+
+        encoding
+            .decode_without_bom_handling_and_without_replacement(&input)
+            .map(|decoded| decoded.into_owned())
+            .ok_or(CcwcError::InvalidEncoding(self))
+    }
+
+    /// The number of bytes that the string takes up in this encoding
+    fn encoded_len(self, input_string: &str) -> usize {
+        match self {
+            Encoding::Utf8 => input_string.len(),
+            Encoding::Latin1 => input_string.chars().count(),
+            Encoding::Utf16Le | Encoding::Utf16Be => 2 * input_string.encode_utf16().count(),
+        }
+    }
+}
+
+/// The formats that the input can be in, which decide what text is counted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InputFormat {
+    /// Plain text, all of which is counted
+    #[default]
+    Text,
+    /// A JSON document, where only the string values are counted
+    #[cfg(feature = "json")]
+    Json,
+    /// Comma separated values, where only the values of the fields are
+    /// counted
+    Csv,
+}
+
+impl InputFormat {
+    /// Extracts the text to count from the decoded input
+    pub fn extract(self, input: String) -> Result<String, CcwcError> {
+        match self {
+            InputFormat::Text => Ok(input),
+            #[cfg(feature = "json")]
+            InputFormat::Json => extract_text_from_json(&input).map_err(CcwcError::InvalidJson),
+            InputFormat::Csv => Ok(extract_text_from_csv(&input)),
+        }
+    }
+}
 
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Latin1 => "Latin-1",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+        };
+        write!(f, "{name}")
+    }
 }
 
 impl Cli {
+    /// Returns true if the counts should be printed each time the input file
+    /// changes using `watch` rather than once using `run`
+    #[cfg(feature = "watch")]
+    pub fn watch_mode(&self) -> bool {
+        self.watch
+    }
+
+    /// Returns true if the counts in a file should be verified using `check`
+    /// rather than printed using `run`
+    pub fn check_mode(&self) -> bool {
+        self.check.is_some()
+    }
+
+    /// Returns an error if a count of any of the results is outside of the
+    /// bounds given by `--assert-min` and `--assert-max`, or if there are
+    /// bounds but not exactly one count was specified
+    fn check_bounds(&self, results: &[CountResult]) -> Result<(), CcwcError> {
+        if self.assert_min.is_none() && self.assert_max.is_none() {
+            return Ok(());
+        }
+        if self.count_flags_set() != 1 {
+            return Err(CcwcError::InvalidArguments(
+                "--assert-min and --assert-max need exactly one count, such as -l".to_owned(),
+            ));
+        }
+
+        for result in results {
+            let label = result.filename.as_deref().unwrap_or("stdin");
+            for (name, count) in result.named_counts() {
+                if let Some(min) = self.assert_min.filter(|&min| count.is_below(min)) {
+                    return Err(CcwcError::CountOutOfBounds(format!(
+                        "{label}: {name} is {count}, less than the minimum of {min}"
+                    )));
+                }
+                if let Some(max) = self.assert_max.filter(|&max| count.is_above(max)) {
+                    return Err(CcwcError::CountOutOfBounds(format!(
+                        "{label}: {name} is {count}, more than the maximum of {max}"
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if only the exit status should say whether a count is
+    /// zero using `run_quiet` rather than the counts being printed using `run`
+    pub fn quiet_mode(&self) -> bool {
+        self.quiet
+    }
+
+    /// Returns true if the output is written to stdout rather than to a file
+    fn writes_to_stdout(&self) -> bool {
+        self.output_file.is_none() && self.output_append.is_none()
+    }
+
+    /// Returns true if the counts of an input are within the `--min-*` and
+    /// `--max-*` limits, ignoring the limits of any count that was not made
+    fn in_limits(&self, result: &CountResult) -> bool {
+        let in_range = |count: Option<usize>, min: Option<usize>, max: Option<usize>| {
+            count.is_none_or(|count| {
+                min.is_none_or(|min| count >= min) && max.is_none_or(|max| count <= max)
+            })
+        };
+
+        in_range(result.lines, self.min_lines, self.max_lines)
+            && in_range(result.words, self.min_words, self.max_words)
+            && in_range(result.bytes, self.min_bytes, self.max_bytes)
+    }
+
+    /// Returns true if the filenames, and the `total` label, should not be
+    /// printed after the counts
+    fn omits_filenames(&self) -> bool {
+        self.no_filename || self.number
+    }
+
+    /// The name to print after the counts of stdin, if there is one
+    fn stdin_label(&self) -> Option<String> {
+        match &self.stdin_label {
+            Some(label) => (!label.is_empty()).then(|| label.clone()),
+            None => self.posix.then(|| "-".to_owned()),
+        }
+    }
+
+    /// The shell to print the completion script for instead of counting, if
+    /// one was given with `--generate-completions`
+    pub fn completions_shell(&self) -> Option<Shell> {
+        self.generate_completions
+    }
+
+    /// Returns true if the CountConfig resolved from the other options should
+    /// be printed instead of counting
+    pub fn print_config_mode(&self) -> bool {
+        self.print_config
+    }
+
     /// Returns true if any command line flag has been set, false otherwise
     fn any_flag_set(&self) -> bool {
-        self.byte_count || self.word_count || self.line_count || self.char_count
+        self.count_flags_set() > 0
+    }
+
+    /// The number of flags that have been set to choose a count
+    fn count_flags_set(&self) -> usize {
+        [
+            self.byte_count,
+            self.word_count,
+            self.line_count,
+            self.char_count,
+            self.grapheme_count,
+            self.max_line_length,
+            self.max_word_length,
+            self.line_stats,
+            self.unique_words,
+            self.unique_lines,
+            self.duplicate_lines,
+            self.paragraph_count,
+            self.non_blank,
+            self.tab_count,
+            self.space_count,
+            self.control_chars,
+            self.non_ascii,
+            self.windows_line_endings,
+            self.counts_matching_lines(),
+            self.sentence_count,
+            self.average_word_length,
+            self.average_line_length,
+        ]
+        .into_iter()
+        .filter(|&set| set)
+        .count()
+    }
+
+    /// Returns true if `--lines-containing` has been given
+    #[cfg(feature = "regex")]
+    fn counts_matching_lines(&self) -> bool {
+        self.lines_containing.is_some()
+    }
+
+    /// Returns true if `--lines-containing` has been given, which it cannot
+    /// be without the regex feature
+    #[cfg(not(feature = "regex"))]
+    fn counts_matching_lines(&self) -> bool {
+        false
     }
 }
 
-/// An enum that breaks out the options for counting characters or bytes
-#[derive(Debug)]
+/// A compiled regular expression that lines or words are matched against,
+/// from `--lines-containing` or `--word-regex`. Two patterns are equal if they
+/// were compiled from the same string, and they are serialized as that string.
+#[cfg(feature = "regex")]
+#[derive(Debug, Clone)]
+struct RegexPattern(Regex);
+
+#[cfg(feature = "regex")]
+impl PartialEq for RegexPattern {
+    fn eq(&self, other: &RegexPattern) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+#[cfg(feature = "regex")]
+impl Eq for RegexPattern {}
+
+#[cfg(all(feature = "regex", feature = "serde"))]
+impl serde::Serialize for RegexPattern {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+#[cfg(all(feature = "regex", feature = "serde"))]
+impl<'de> serde::Deserialize<'de> for RegexPattern {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<RegexPattern, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern).map(RegexPattern).map_err(serde::de::Error::custom)
+    }
+}
+
+/// The ways of counting words
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum WordMode {
+    /// Words are runs of non-whitespace characters
+    Whitespace,
+    /// Words are the fields of each line separated by the character
+    Delimiter(char),
+    /// Words are the non-overlapping matches of the regular expression
+    #[cfg(feature = "regex")]
+    Regex(RegexPattern),
+    /// Words are not counted
+    None,
+}
+
+impl WordMode {
+    /// How the words should be counted if they are counted, given the word
+    /// pattern and the delimiter. A word pattern takes precedence over a
+    /// delimiter, and words are whitespace separated if there is neither.
+    fn from_flags(
+        #[cfg(feature = "regex")] word_pattern: Option<&Regex>,
+        delimiter: Option<char>,
+    ) -> WordMode {
+        #[cfg(feature = "regex")]
+        if let Some(pattern) = word_pattern {
+            return WordMode::Regex(RegexPattern(pattern.clone()));
+        }
+
+        match delimiter {
+            Some(delimiter) => WordMode::Delimiter(delimiter),
+            None => WordMode::Whitespace,
+        }
+    }
+
+    /// Counts the words in the contents, or returns `None` if they are not
+    /// counted
+    fn count(&self, contents: &str) -> Option<usize> {
+        match self {
+            WordMode::Whitespace => Some(count_words(contents)),
+            WordMode::Delimiter(delimiter) => Some(count_fields(contents, *delimiter)),
+            #[cfg(feature = "regex")]
+            WordMode::Regex(RegexPattern(pattern)) => Some(count_words_by_regex(contents, pattern)),
+            WordMode::None => None,
+        }
+    }
+}
 
+/// An enum that breaks out the options for counting characters or bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum CharCount {
+    GraphemeClusters,
     Chars,
     Bytes,
     None,
 }
 
 /// A struct that holds the configuration options for the counts
-#[derive(Debug)]
-struct CountConfig {
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CountConfig {
     count_chars: CharCount,
-    count_words: bool,
+    count_words: WordMode,
     count_lines: bool,
+    count_max_line_length: bool,
+    count_max_word_length: bool,
+    count_line_stats: bool,
+    count_unique_words: bool,
+    count_word_frequency: bool,
+    count_unique_lines: bool,
+    count_duplicate_lines: bool,
+    count_paragraphs: bool,
+    count_non_blank_lines: bool,
+    count_tabs: bool,
+    count_spaces: bool,
+    count_control_chars: bool,
+    include_common_controls: bool,
+    count_non_ascii: bool,
+    non_ascii_bytes: bool,
+    count_crlf: bool,
+    #[cfg(feature = "regex")]
+    count_lines_matching: Option<RegexPattern>,
+    count_sentences: bool,
+    count_average_word_length: bool,
+    count_average_line_length: bool,
+    line_length_histogram: Option<NonZeroUsize>,
+    ignore_case: bool,
+    crlf_mode: bool,
+    strip_bom: bool,
+    trim_whitespace: bool,
+    comment_style: Option<CommentStyle>,
+    logical_lines: bool,
+    line_separator: u8,
+    encoding: Encoding,
+    detect_encoding: bool,
+    input_format: InputFormat,
+    locale: Locale,
+    #[cfg(feature = "mmap")]
+    mmap_threshold: u64,
+    skip_binary: bool,
+    progress: ProgressMode,
+    progress_threshold: u64,
+    limit: Option<u64>,
+    no_filename: bool,
     filename: Option<String>,
 }
 
 impl CountConfig {
     /// Create a CountConfig from the given cli options
     pub fn from_cli(cli: &Cli) -> CountConfig {
-        return  CountConfig {
-            count_chars: if cli.char_count {
+        CountConfig {
+            count_chars: if cli.grapheme_count {
+                CharCount::GraphemeClusters
+            } else if cli.char_count {
                 CharCount::Chars
             } else if cli.byte_count || !cli.any_flag_set() {
                 CharCount::Bytes
@@ -75,256 +1241,6336 @@ impl CountConfig {
                 CharCount::None
             },
             count_lines: cli.line_count || !cli.any_flag_set(),
-            count_words: cli.word_count || !cli.any_flag_set(),
-            filename: cli.filename.clone(),
+            count_words: if cli.word_count || !cli.any_flag_set() {
+                WordMode::from_flags(
+                    #[cfg(feature = "regex")]
+                    cli.word_regex.as_ref(),
+                    cli.delimiter,
+                )
+            } else {
+                WordMode::None
+            },
+            count_max_line_length: cli.max_line_length,
+            count_max_word_length: cli.max_word_length,
+            count_line_stats: cli.line_stats,
+            count_unique_words: cli.unique_words,
+            count_word_frequency: cli.frequency,
+            count_unique_lines: cli.unique_lines,
+            count_duplicate_lines: cli.duplicate_lines,
+            count_paragraphs: cli.paragraph_count,
+            count_non_blank_lines: cli.non_blank,
+            count_tabs: cli.tab_count,
+            count_spaces: cli.space_count,
+            count_control_chars: cli.control_chars,
+            include_common_controls: cli.include_common_controls,
+            count_non_ascii: cli.non_ascii,
+            non_ascii_bytes: cli.non_ascii_bytes,
+            count_crlf: cli.windows_line_endings,
+            #[cfg(feature = "regex")]
+            count_lines_matching: cli.lines_containing.clone().map(RegexPattern),
+            count_sentences: cli.sentence_count,
+            count_average_word_length: cli.average_word_length,
+            count_average_line_length: cli.average_line_length,
+            line_length_histogram: cli
+                .histogram
+                .then_some(cli.histogram_buckets)
+                .and_then(NonZeroUsize::new),
+            ignore_case: cli.ignore_case,
+            crlf_mode: cli.crlf,
+            strip_bom: cli.strip_bom,
+            trim_whitespace: cli.trim_whitespace,
+            comment_style: cli.comment_strip,
+            logical_lines: cli.logical_lines,
+            line_separator: if cli.null_data { b'\0' } else { b'\n' },
+            encoding: cli.encoding,
+            detect_encoding: cli.encoding_detect,
+            input_format: cli.input_format,
+            locale: cli.locale,
+            #[cfg(feature = "mmap")]
+            mmap_threshold: cli.mmap_threshold.unwrap_or(DEFAULT_MMAP_THRESHOLD),
+            skip_binary: cli.skip_binary,
+            progress: if cli.progress {
+                ProgressMode::Always
+            } else if cli.no_progress {
+                ProgressMode::Never
+            } else {
+                ProgressMode::Auto
+            },
+            progress_threshold: cli.progress_threshold.unwrap_or(DEFAULT_PROGRESS_THRESHOLD),
+            limit: cli.limit,
+            no_filename: cli.omits_filenames(),
+            // Files are counted with their own names, so this is only used
+            // for stdin
+            filename: cli.stdin_label(),
+        }
+    }
+
+    /// Returns true if any of the counts in the config can only be made with
+    /// the whole of the input in memory at once
+    fn needs_whole_input(&self) -> bool {
+        self.count_unique_words
+            || self.count_word_frequency
+            || self.count_unique_lines
+            || self.count_duplicate_lines
+            || self.count_control_chars
+            || self.count_non_ascii
+            || self.count_crlf
+            || self.count_paragraphs
+            || self.count_non_blank_lines
+            || self.counts_matching_lines()
+            || !matches!(self.count_words, WordMode::Whitespace | WordMode::None)
+            || self.count_sentences
+            || self.count_average_word_length
+            || self.count_average_line_length
+            || self.line_length_histogram.is_some()
+            || self.count_chars == CharCount::GraphemeClusters
+            || (self.count_chars == CharCount::Chars && self.locale != Locale::Utf8)
+            || self.encoding != Encoding::Utf8
+            || self.input_format != InputFormat::Text
+            || self.detect_encoding
+            || self.strip_bom
+            || self.trim_whitespace
+            || self.comment_style.is_some()
+            || self.logical_lines
+    }
+
+    /// Returns true if the lines matching a pattern should be counted
+    #[cfg(feature = "regex")]
+    fn counts_matching_lines(&self) -> bool {
+        self.count_lines_matching.is_some()
+    }
+
+    /// Returns true if the lines matching a pattern should be counted, which
+    /// they cannot be without the regex feature
+    #[cfg(not(feature = "regex"))]
+    fn counts_matching_lines(&self) -> bool {
+        false
+    }
+
+    /// The size from which the progress of reading a file should be printed,
+    /// or `None` if it should not be printed
+    fn progress_threshold(&self) -> Option<u64> {
+        let show = match self.progress {
+            ProgressMode::Auto => io::stderr().is_terminal(),
+            ProgressMode::Always => true,
+            ProgressMode::Never => false,
+        };
+        show.then_some(self.progress_threshold)
+    }
+
+    /// Reads the input, stopping after the limit in the config if there is
+    /// one. All of the bytes up to the limit are kept, even if it cuts the
+    /// final character short, see `drop_cut_character`.
+    fn read_input<R: Read>(&self, reader: R) -> Result<Vec<u8>, CcwcError> {
+        let mut input = Vec::new();
+        match self.limit {
+            Some(limit) => reader.take(limit).read_to_end(&mut input)?,
+            None => {
+                let mut reader = reader;
+                reader.read_to_end(&mut input)?
+            }
+        };
+        Ok(input)
+    }
+
+    /// If the input was cut off at the limit in the config part of the way
+    /// through its final character, drops the rest of that character so that
+    /// the input can still be decoded. Only the byte count includes it.
+    fn drop_cut_character(&self, input: &mut Vec<u8>) {
+        if self.limit != Some(input.len() as u64) {
+            return;
+        }
+        let complete = match self.encoding {
+            Encoding::Utf8 => match std::str::from_utf8(input) {
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                _ => input.len(),
+            },
+            Encoding::Latin1 => input.len(),
+            Encoding::Utf16Le | Encoding::Utf16Be => input.len() - input.len() % 2,
+        };
+        input.truncate(complete);
+    }
+
+    /// Returns true if the other config makes the same counts as this one in
+    /// the same way, so that the counts made with them can be combined
+    fn has_same_counts(&self, other: &CountConfig) -> bool {
+        self.count_chars == other.count_chars
+            && self.count_words == other.count_words
+            && self.count_lines == other.count_lines
+            && self.count_max_line_length == other.count_max_line_length
+            && self.count_max_word_length == other.count_max_word_length
+            && self.count_line_stats == other.count_line_stats
+            && self.count_unique_words == other.count_unique_words
+            && self.count_word_frequency == other.count_word_frequency
+            && self.count_unique_lines == other.count_unique_lines
+            && self.count_duplicate_lines == other.count_duplicate_lines
+            && self.count_paragraphs == other.count_paragraphs
+            && self.count_non_blank_lines == other.count_non_blank_lines
+            && self.count_tabs == other.count_tabs
+            && self.count_spaces == other.count_spaces
+            && self.count_control_chars == other.count_control_chars
+            && self.include_common_controls == other.include_common_controls
+            && self.count_non_ascii == other.count_non_ascii
+            && self.non_ascii_bytes == other.non_ascii_bytes
+            && self.count_crlf == other.count_crlf
+            && self.counts_same_matching_lines(other)
+            && self.count_sentences == other.count_sentences
+            && self.count_average_word_length == other.count_average_word_length
+            && self.count_average_line_length == other.count_average_line_length
+            && self.line_length_histogram == other.line_length_histogram
+            && self.ignore_case == other.ignore_case
+            && self.crlf_mode == other.crlf_mode
+            && self.strip_bom == other.strip_bom
+            && self.trim_whitespace == other.trim_whitespace
+            && self.comment_style == other.comment_style
+            && self.logical_lines == other.logical_lines
+            && self.line_separator == other.line_separator
+            && self.encoding == other.encoding
+            && self.detect_encoding == other.detect_encoding
+            && self.input_format == other.input_format
+            && self.locale == other.locale
+    }
+
+    /// Returns true if both configs count the lines matching the same pattern,
+    /// or neither does
+    #[cfg(feature = "regex")]
+    fn counts_same_matching_lines(&self, other: &CountConfig) -> bool {
+        self.count_lines_matching == other.count_lines_matching
+    }
+
+    /// Returns true if both configs count the lines matching the same pattern,
+    /// which neither can without the regex feature
+    #[cfg(not(feature = "regex"))]
+    fn counts_same_matching_lines(&self, _other: &CountConfig) -> bool {
+        true
+    }
+
+    /// Create a CountConfigBuilder with every count turned off
+    pub fn builder() -> CountConfigBuilder {
+        CountConfigBuilder::default()
+    }
+
+    /// Create a CountConfig that only counts bytes
+    ///
+    /// ```
+    /// let result = ccwc::count_streaming(&b"hello, world"[..], &ccwc::CountConfig::for_bytes());
+    /// assert_eq!(result.unwrap().bytes, Some(12));
+    /// ```
+    pub fn for_bytes() -> CountConfig {
+        CountConfig::builder().bytes(true).build()
+    }
+
+    /// Create a CountConfig that only counts words
+    pub fn for_words() -> CountConfig {
+        CountConfig::builder().words(true).build()
+    }
+
+    /// Create a CountConfig that only counts lines
+    pub fn for_lines() -> CountConfig {
+        CountConfig::builder().lines(true).build()
+    }
+
+    /// Create a CountConfig that only counts characters
+    pub fn for_chars() -> CountConfig {
+        CountConfig::builder().chars(true).build()
+    }
+
+    /// Merges two configs, for example one read from a config file and one
+    /// from the command line. A count or flag is on in the result if it is on
+    /// in either config, and counting grapheme clusters beats counting
+    /// characters, which beats counting bytes, which beats counting neither.
+    /// If both configs count words then they are counted as in `self`.
+    /// Optional settings, such as the filename, the limit, and the pattern
+    /// for `--lines-containing`, are taken from `self` if it has them and
+    /// from `other` otherwise. All other settings, such as the encoding and
+    /// the line separator, are taken from `self`.
+    ///
+    /// ```
+    /// use ccwc::CountConfig;
+    ///
+    /// let config = CountConfig::for_words().union(CountConfig::for_lines());
+    /// assert_eq!(config, CountConfig::builder().words(true).lines(true).build());
+    /// ```
+    pub fn union(self, other: CountConfig) -> CountConfig {
+        CountConfig {
+            count_chars: match (self.count_chars, other.count_chars) {
+                (CharCount::GraphemeClusters, _) | (_, CharCount::GraphemeClusters) => {
+                    CharCount::GraphemeClusters
+                }
+                (CharCount::Chars, _) | (_, CharCount::Chars) => CharCount::Chars,
+                (CharCount::Bytes, _) | (_, CharCount::Bytes) => CharCount::Bytes,
+                (CharCount::None, CharCount::None) => CharCount::None,
+            },
+            count_words: if self.count_words == WordMode::None {
+                other.count_words
+            } else {
+                self.count_words
+            },
+            count_lines: self.count_lines || other.count_lines,
+            count_max_line_length: self.count_max_line_length || other.count_max_line_length,
+            count_max_word_length: self.count_max_word_length || other.count_max_word_length,
+            count_line_stats: self.count_line_stats || other.count_line_stats,
+            count_unique_words: self.count_unique_words || other.count_unique_words,
+            count_word_frequency: self.count_word_frequency || other.count_word_frequency,
+            count_unique_lines: self.count_unique_lines || other.count_unique_lines,
+            count_duplicate_lines: self.count_duplicate_lines || other.count_duplicate_lines,
+            count_paragraphs: self.count_paragraphs || other.count_paragraphs,
+            count_non_blank_lines: self.count_non_blank_lines || other.count_non_blank_lines,
+            count_tabs: self.count_tabs || other.count_tabs,
+            count_spaces: self.count_spaces || other.count_spaces,
+            count_control_chars: self.count_control_chars || other.count_control_chars,
+            include_common_controls: self.include_common_controls || other.include_common_controls,
+            count_non_ascii: self.count_non_ascii || other.count_non_ascii,
+            non_ascii_bytes: self.non_ascii_bytes || other.non_ascii_bytes,
+            count_crlf: self.count_crlf || other.count_crlf,
+            #[cfg(feature = "regex")]
+            count_lines_matching: self.count_lines_matching.or(other.count_lines_matching),
+            count_sentences: self.count_sentences || other.count_sentences,
+            count_average_word_length: self.count_average_word_length
+                || other.count_average_word_length,
+            count_average_line_length: self.count_average_line_length
+                || other.count_average_line_length,
+            line_length_histogram: self.line_length_histogram.or(other.line_length_histogram),
+            ignore_case: self.ignore_case || other.ignore_case,
+            crlf_mode: self.crlf_mode || other.crlf_mode,
+            strip_bom: self.strip_bom || other.strip_bom,
+            trim_whitespace: self.trim_whitespace || other.trim_whitespace,
+            comment_style: self.comment_style.or(other.comment_style),
+            logical_lines: self.logical_lines || other.logical_lines,
+            line_separator: self.line_separator,
+            encoding: self.encoding,
+            detect_encoding: self.detect_encoding || other.detect_encoding,
+            input_format: self.input_format,
+            locale: self.locale,
+            #[cfg(feature = "mmap")]
+            mmap_threshold: self.mmap_threshold,
+            skip_binary: self.skip_binary || other.skip_binary,
+            progress: self.progress,
+            progress_threshold: self.progress_threshold,
+            limit: self.limit.or(other.limit),
+            no_filename: self.no_filename || other.no_filename,
+            filename: self.filename.or(other.filename),
+        }
+    }
+}
+
+/// The CountConfig used when no flags are given on the command line, which
+/// counts lines, words, and bytes
+impl Default for CountConfig {
+    fn default() -> CountConfig {
+        CountConfig {
+            count_chars: CharCount::Bytes,
+            count_words: WordMode::Whitespace,
+            count_lines: true,
+            count_max_line_length: false,
+            count_max_word_length: false,
+            count_line_stats: false,
+            count_unique_words: false,
+            count_word_frequency: false,
+            count_unique_lines: false,
+            count_duplicate_lines: false,
+            count_paragraphs: false,
+            count_non_blank_lines: false,
+            count_tabs: false,
+            count_spaces: false,
+            count_control_chars: false,
+            include_common_controls: false,
+            count_non_ascii: false,
+            non_ascii_bytes: false,
+            count_crlf: false,
+            #[cfg(feature = "regex")]
+            count_lines_matching: None,
+            count_sentences: false,
+            count_average_word_length: false,
+            count_average_line_length: false,
+            line_length_histogram: None,
+            ignore_case: false,
+            crlf_mode: false,
+            strip_bom: false,
+            trim_whitespace: false,
+            comment_style: None,
+            logical_lines: false,
+            line_separator: b'\n',
+            encoding: Encoding::Utf8,
+            detect_encoding: false,
+            input_format: InputFormat::Text,
+            locale: Locale::Utf8,
+            #[cfg(feature = "mmap")]
+            mmap_threshold: DEFAULT_MMAP_THRESHOLD,
+            skip_binary: false,
+            progress: ProgressMode::Auto,
+            progress_threshold: DEFAULT_PROGRESS_THRESHOLD,
+            limit: None,
+            no_filename: false,
+            filename: None,
         }
     }
-}
+}
+
+/// A builder for CountConfig, for library users who are not parsing command
+/// line arguments. Unlike the command line no counts are made by default, each
+/// one has to be turned on. As with `-m`, counting characters takes priority
+/// over counting bytes.
+#[derive(Debug, Default, Clone)]
+pub struct CountConfigBuilder {
+    lines: bool,
+    words: bool,
+    bytes: bool,
+    chars: bool,
+    graphemes: bool,
+    max_line_length: bool,
+    max_word_length: bool,
+    line_stats: bool,
+    unique_words: bool,
+    word_frequency: bool,
+    unique_lines: bool,
+    duplicate_lines: bool,
+    paragraphs: bool,
+    non_blank_lines: bool,
+    tabs: bool,
+    spaces: bool,
+    control_chars: bool,
+    include_common_controls: bool,
+    non_ascii: bool,
+    non_ascii_bytes: bool,
+    crlf_line_endings: bool,
+    #[cfg(feature = "regex")]
+    lines_matching: Option<Regex>,
+    #[cfg(feature = "regex")]
+    word_pattern: Option<Regex>,
+    delimiter: Option<char>,
+    sentences: bool,
+    average_word_length: bool,
+    average_line_length: bool,
+    line_length_histogram: Option<NonZeroUsize>,
+    ignore_case: bool,
+    crlf: bool,
+    strip_bom: bool,
+    trim_whitespace: bool,
+    comment_style: Option<CommentStyle>,
+    logical_lines: bool,
+    null_data: bool,
+    encoding: Encoding,
+    detect_encoding: bool,
+    input_format: InputFormat,
+    locale: Locale,
+    #[cfg(feature = "mmap")]
+    mmap_threshold: Option<u64>,
+    skip_binary: bool,
+    progress: Option<bool>,
+    progress_threshold: Option<u64>,
+    limit: Option<u64>,
+    filename: Option<String>,
+}
+
+impl CountConfigBuilder {
+    /// Set whether to count lines
+    pub fn lines(mut self, lines: bool) -> CountConfigBuilder {
+        self.lines = lines;
+        self
+    }
+
+    /// Set whether to count words
+    pub fn words(mut self, words: bool) -> CountConfigBuilder {
+        self.words = words;
+        self
+    }
+
+    /// Set whether to count bytes. Ignored if characters are counted.
+    pub fn bytes(mut self, bytes: bool) -> CountConfigBuilder {
+        self.bytes = bytes;
+        self
+    }
+
+    /// Set whether to count characters
+    pub fn chars(mut self, chars: bool) -> CountConfigBuilder {
+        self.chars = chars;
+        self
+    }
+
+    /// Set whether to count grapheme clusters, which takes precedence over
+    /// counting characters or bytes
+    pub fn graphemes(mut self, graphemes: bool) -> CountConfigBuilder {
+        self.graphemes = graphemes;
+        self
+    }
+
+    /// Set whether to find the length of the longest line
+    pub fn max_line_length(mut self, max_line_length: bool) -> CountConfigBuilder {
+        self.max_line_length = max_line_length;
+        self
+    }
+
+    /// Set whether to find the length of the longest word
+    pub fn max_word_length(mut self, max_word_length: bool) -> CountConfigBuilder {
+        self.max_word_length = max_word_length;
+        self
+    }
+
+    /// Set whether to find the shortest, longest, and mean line lengths
+    pub fn line_stats(mut self, line_stats: bool) -> CountConfigBuilder {
+        self.line_stats = line_stats;
+        self
+    }
+
+    /// Set whether to count the distinct words
+    pub fn unique_words(mut self, unique_words: bool) -> CountConfigBuilder {
+        self.unique_words = unique_words;
+        self
+    }
+
+    /// Set whether to count how many times each word occurs
+    pub fn word_frequency(mut self, word_frequency: bool) -> CountConfigBuilder {
+        self.word_frequency = word_frequency;
+        self
+    }
+
+    /// Set whether to count the distinct lines
+    pub fn unique_lines(mut self, unique_lines: bool) -> CountConfigBuilder {
+        self.unique_lines = unique_lines;
+        self
+    }
+
+    /// Set whether to count the lines that repeat an earlier line
+    pub fn duplicate_lines(mut self, duplicate_lines: bool) -> CountConfigBuilder {
+        self.duplicate_lines = duplicate_lines;
+        self
+    }
+
+    /// Set whether to count paragraphs
+    pub fn paragraphs(mut self, paragraphs: bool) -> CountConfigBuilder {
+        self.paragraphs = paragraphs;
+        self
+    }
+
+    /// Set whether to count the lines that are not blank
+    pub fn non_blank_lines(mut self, non_blank_lines: bool) -> CountConfigBuilder {
+        self.non_blank_lines = non_blank_lines;
+        self
+    }
+
+    /// Set whether to count tab characters
+    pub fn tabs(mut self, tabs: bool) -> CountConfigBuilder {
+        self.tabs = tabs;
+        self
+    }
+
+    /// Set whether to count ASCII space characters
+    pub fn spaces(mut self, spaces: bool) -> CountConfigBuilder {
+        self.spaces = spaces;
+        self
+    }
+
+    /// Set whether to count ASCII control characters
+    pub fn control_chars(mut self, control_chars: bool) -> CountConfigBuilder {
+        self.control_chars = control_chars;
+        self
+    }
+
+    /// Set whether tabs, newlines, and carriage returns are counted as
+    /// control characters
+    pub fn include_common_controls(mut self, include_common_controls: bool) -> CountConfigBuilder {
+        self.include_common_controls = include_common_controls;
+        self
+    }
+
+    /// Set whether to count non-ASCII characters
+    pub fn non_ascii(mut self, non_ascii: bool) -> CountConfigBuilder {
+        self.non_ascii = non_ascii;
+        self
+    }
+
+    /// Set whether the bytes of non-ASCII characters in UTF-8 are counted
+    /// rather than the characters
+    pub fn non_ascii_bytes(mut self, non_ascii_bytes: bool) -> CountConfigBuilder {
+        self.non_ascii_bytes = non_ascii_bytes;
+        self
+    }
+
+    /// Set whether to count Windows `\r\n` line endings
+    pub fn crlf_line_endings(mut self, crlf_line_endings: bool) -> CountConfigBuilder {
+        self.crlf_line_endings = crlf_line_endings;
+        self
+    }
+
+    /// Set the regular expression to count the matching lines of, or `None`
+    /// to not count them
+    #[cfg(feature = "regex")]
+    pub fn lines_matching(mut self, pattern: Option<Regex>) -> CountConfigBuilder {
+        self.lines_matching = pattern;
+        self
+    }
+
+    /// Set the character that separates the fields of each line, which are
+    /// then counted as words, or `None` to count whitespace separated words.
+    /// A word pattern takes precedence over a delimiter.
+    pub fn delimiter(mut self, delimiter: Option<char>) -> CountConfigBuilder {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Set the regular expression whose matches are counted as words, or
+    /// `None` to count runs of non-whitespace characters
+    #[cfg(feature = "regex")]
+    pub fn word_pattern(mut self, pattern: Option<Regex>) -> CountConfigBuilder {
+        self.word_pattern = pattern;
+        self
+    }
+
+    /// Set whether to count sentences
+    pub fn sentences(mut self, sentences: bool) -> CountConfigBuilder {
+        self.sentences = sentences;
+        self
+    }
+
+    /// Set whether to find the mean length of the words in characters
+    pub fn average_word_length(mut self, average_word_length: bool) -> CountConfigBuilder {
+        self.average_word_length = average_word_length;
+        self
+    }
+
+    /// Set whether to find the mean length of the lines in characters
+    pub fn average_line_length(mut self, average_line_length: bool) -> CountConfigBuilder {
+        self.average_line_length = average_line_length;
+        self
+    }
+
+    /// Set the number of ranges of line lengths to make a histogram of, or
+    /// `None` not to make one
+    pub fn line_length_histogram(mut self, buckets: Option<NonZeroUsize>) -> CountConfigBuilder {
+        self.line_length_histogram = buckets;
+        self
+    }
+
+    /// Set whether to ignore case when deciding whether words or lines are
+    /// distinct
+    pub fn ignore_case(mut self, ignore_case: bool) -> CountConfigBuilder {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    /// Set whether to treat `\r\n` line endings as `\n`
+    pub fn crlf(mut self, crlf: bool) -> CountConfigBuilder {
+        self.crlf = crlf;
+        self
+    }
+
+    /// Set whether to remove a UTF-8 byte order mark from the start of the
+    /// input before counting it
+    pub fn strip_bom(mut self, strip_bom: bool) -> CountConfigBuilder {
+        self.strip_bom = strip_bom;
+        self
+    }
+
+    /// Set whether to remove the whitespace from the start and end of each
+    /// line before counting everything but the bytes
+    pub fn trim_whitespace(mut self, trim_whitespace: bool) -> CountConfigBuilder {
+        self.trim_whitespace = trim_whitespace;
+        self
+    }
+
+    /// Set the style of the comments to remove before counting everything
+    /// but the bytes, if any
+    pub fn comment_style(mut self, comment_style: Option<CommentStyle>) -> CountConfigBuilder {
+        self.comment_style = comment_style;
+        self
+    }
+
+    /// Set whether logical lines are counted rather than line separators, so
+    /// that a final line without a separator is counted
+    pub fn logical_lines(mut self, logical_lines: bool) -> CountConfigBuilder {
+        self.logical_lines = logical_lines;
+        self
+    }
+
+    /// Set whether lines are separated by NUL characters rather than newlines
+    pub fn null_data(mut self, null_data: bool) -> CountConfigBuilder {
+        self.null_data = null_data;
+        self
+    }
+
+    /// Set the character encoding of the input. Bytes are counted in this
+    /// encoding.
+    pub fn encoding(mut self, encoding: Encoding) -> CountConfigBuilder {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Set whether to guess the encoding of the input and decode it from that
+    /// encoding rather than the one set with `encoding`
+    pub fn detect_encoding(mut self, detect_encoding: bool) -> CountConfigBuilder {
+        self.detect_encoding = detect_encoding;
+        self
+    }
+
+    /// Set the format of the input, which decides what text in it is counted
+    pub fn input_format(mut self, input_format: InputFormat) -> CountConfigBuilder {
+        self.input_format = input_format;
+        self
+    }
+
+    /// Set the locale that decides what is counted as a character
+    pub fn locale(mut self, locale: Locale) -> CountConfigBuilder {
+        self.locale = locale;
+        self
+    }
+
+    /// Set the size in bytes from which files are memory mapped rather than
+    /// read into memory. Defaults to `DEFAULT_MMAP_THRESHOLD`.
+    #[cfg(feature = "mmap")]
+    pub fn mmap_threshold(mut self, mmap_threshold: u64) -> CountConfigBuilder {
+        self.mmap_threshold = Some(mmap_threshold);
+        self
+    }
+
+    /// Set whether files that look like they are binary are skipped rather
+    /// than counted
+    pub fn skip_binary(mut self, skip_binary: bool) -> CountConfigBuilder {
+        self.skip_binary = skip_binary;
+        self
+    }
+
+    /// Set whether the progress of reading large files is printed to stderr.
+    /// By default it is printed if stderr is a terminal.
+    pub fn progress(mut self, progress: bool) -> CountConfigBuilder {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Set the size in bytes from which the progress of reading a file is
+    /// printed. Defaults to 64MiB.
+    pub fn progress_threshold(mut self, progress_threshold: u64) -> CountConfigBuilder {
+        self.progress_threshold = Some(progress_threshold);
+        self
+    }
+
+    /// Set the maximum number of bytes of each input to count
+    pub fn limit(mut self, limit: Option<u64>) -> CountConfigBuilder {
+        self.limit = limit;
+        self
+    }
+
+    /// Set the filename that the counts are labelled with
+    pub fn filename(mut self, filename: Option<String>) -> CountConfigBuilder {
+        self.filename = filename;
+        self
+    }
+
+    /// Create the CountConfig
+    pub fn build(self) -> CountConfig {
+        CountConfig {
+            count_chars: if self.graphemes {
+                CharCount::GraphemeClusters
+            } else if self.chars {
+                CharCount::Chars
+            } else if self.bytes {
+                CharCount::Bytes
+            } else {
+                CharCount::None
+            },
+            count_lines: self.lines,
+            count_words: if self.words {
+                WordMode::from_flags(
+                    #[cfg(feature = "regex")]
+                    self.word_pattern.as_ref(),
+                    self.delimiter,
+                )
+            } else {
+                WordMode::None
+            },
+            count_max_line_length: self.max_line_length,
+            count_max_word_length: self.max_word_length,
+            count_line_stats: self.line_stats,
+            count_unique_words: self.unique_words,
+            count_word_frequency: self.word_frequency,
+            count_unique_lines: self.unique_lines,
+            count_duplicate_lines: self.duplicate_lines,
+            count_paragraphs: self.paragraphs,
+            count_non_blank_lines: self.non_blank_lines,
+            count_tabs: self.tabs,
+            count_spaces: self.spaces,
+            count_control_chars: self.control_chars,
+            include_common_controls: self.include_common_controls,
+            count_non_ascii: self.non_ascii,
+            non_ascii_bytes: self.non_ascii_bytes,
+            count_crlf: self.crlf_line_endings,
+            #[cfg(feature = "regex")]
+            count_lines_matching: self.lines_matching.map(RegexPattern),
+            count_sentences: self.sentences,
+            count_average_word_length: self.average_word_length,
+            count_average_line_length: self.average_line_length,
+            line_length_histogram: self.line_length_histogram,
+            ignore_case: self.ignore_case,
+            crlf_mode: self.crlf,
+            strip_bom: self.strip_bom,
+            trim_whitespace: self.trim_whitespace,
+            comment_style: self.comment_style,
+            logical_lines: self.logical_lines,
+            line_separator: if self.null_data { b'\0' } else { b'\n' },
+            encoding: self.encoding,
+            detect_encoding: self.detect_encoding,
+            input_format: self.input_format,
+            locale: self.locale,
+            #[cfg(feature = "mmap")]
+            mmap_threshold: self.mmap_threshold.unwrap_or(DEFAULT_MMAP_THRESHOLD),
+            skip_binary: self.skip_binary,
+            progress: match self.progress {
+                Some(true) => ProgressMode::Always,
+                Some(false) => ProgressMode::Never,
+                None => ProgressMode::Auto,
+            },
+            progress_threshold: self.progress_threshold.unwrap_or(DEFAULT_PROGRESS_THRESHOLD),
+            limit: self.limit,
+            no_filename: false,
+            filename: self.filename,
+        }
+    }
+}
+
+/// A struct to hold the counts of bytes or characters, words, and lines in a file and the filename
+/// Each of the counts is an optional usize and the filename is a required string
+///
+/// ```
+/// use ccwc::{CountConfig, Counter};
+///
+/// let first = Counter::new(CountConfig::for_words()).count("one two\n");
+/// let second = Counter::new(CountConfig::for_words()).count("three\n");
+/// assert_eq!(first.merge(second).unwrap().result().words, Some(3));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counter {
+    config: CountConfig,
+    byte_or_char_count: Option<usize>,
+    word_count: Option<usize>,
+    line_count: Option<usize>,
+    max_line_length: Option<usize>,
+    max_word_length: Option<usize>,
+    line_lengths: Option<LineLengths>,
+    unique_word_count: Option<usize>,
+    word_frequency: Option<HashMap<String, usize>>,
+    unique_line_count: Option<usize>,
+    duplicate_line_count: Option<usize>,
+    paragraph_count: Option<usize>,
+    non_blank_line_count: Option<usize>,
+    tab_count: Option<usize>,
+    space_count: Option<usize>,
+    control_char_count: Option<usize>,
+    non_ascii_count: Option<usize>,
+    crlf_count: Option<usize>,
+    matching_line_count: Option<usize>,
+    sentence_count: Option<usize>,
+    word_lengths: Option<LengthTotal>,
+    line_char_lengths: Option<LengthTotal>,
+    line_length_histogram: Option<Histogram>,
+    detected_encoding: Option<&'static encoding_rs::Encoding>,
+}
+
+impl Counter {
+    /// A function to create a new Counter struct with the given filename
+    /// and all counts set to None
+    pub fn new(config: CountConfig) -> Counter {
+        Counter {
+            config,
+            byte_or_char_count: None,
+            word_count: None,
+            line_count: None,
+            max_line_length: None,
+            max_word_length: None,
+            line_lengths: None,
+            unique_word_count: None,
+            word_frequency: None,
+            unique_line_count: None,
+            duplicate_line_count: None,
+            paragraph_count: None,
+            non_blank_line_count: None,
+            tab_count: None,
+            space_count: None,
+            control_char_count: None,
+            non_ascii_count: None,
+            crlf_count: None,
+            matching_line_count: None,
+            sentence_count: None,
+            word_lengths: None,
+            line_char_lengths: None,
+            line_length_histogram: None,
+            detected_encoding: None,
+        }
+    }
+
+    /// Decodes the raw input in the encoding of the config and then
+    /// calculates the counts specified in the config as `count` does, except
+    /// that the byte count is the number of bytes in the raw input rather than
+    /// the length of the decoded string encoded again. With `--crlf` the byte
+    /// count is still that of the input once its line endings are normalized.
+    /// With `--strip-bom` a UTF-8 byte order mark is removed first. With an
+    /// `--input-format` other than text, the bytes are those of the text
+    /// extracted from the input. With `--encoding-detect` the input is decoded
+    /// from the encoding that it looks to be in, and the bytes are counted in
+    /// that encoding, which is given in the result.
+    pub fn count_raw(mut self, mut input: Vec<u8>) -> Result<Counter, CcwcError> {
+        if self.config.strip_bom && self.config.encoding == Encoding::Utf8 {
+            let bom_len = input.len() - strip_bom(&input).len();
+            input.drain(..bom_len);
+        }
+        let byte_count = count_bytes_raw(&input);
+        self.config.drop_cut_character(&mut input);
+
+        let (contents, detected_encoding) =
+            decode_input(input, self.config.encoding, self.config.detect_encoding)?;
+        self.detected_encoding = detected_encoding;
+
+        let contents = self.config.input_format.extract(contents)?;
+        let mut counter = self.count(&contents);
+
+        if counter.config.count_chars == CharCount::Bytes
+            && !counter.config.crlf_mode
+            && counter.config.input_format == InputFormat::Text
+        {
+            counter.byte_or_char_count = Some(byte_count);
+        }
+        Ok(counter)
+    }
+
+    /// The number of bytes that the string takes up in the encoding detected
+    /// by `count_raw`, or else in the encoding of the config
+    fn encoded_len(&self, input_string: &str) -> usize {
+        match self.detected_encoding {
+            Some(encoding) if encoding == encoding_rs::UTF_16LE || encoding == encoding_rs::UTF_16BE => {
+                2 * input_string.encode_utf16().count()
+            }
+            Some(encoding) => encoding.encode(input_string).0.len(),
+            None => self.config.encoding.encoded_len(input_string),
+        }
+    }
+
+    /// Actually calculates the counts specified in the config of the Counter.
+    /// Consumes the Counter and returns it with the counts added to it.
+    #[must_use = "Counter::count() returns a new Counter with counts; the original is consumed"]
+    pub fn count(mut self, contents: &str) -> Counter {
+        // The line endings have to be counted before they are normalized
+        if self.config.count_crlf {
+            self.crlf_count = Some(count_crlf(contents.as_bytes()));
+        }
+
+        let normalized;
+        let contents = if self.config.crlf_mode {
+            normalized = normalize_crlf(contents);
+            normalized.as_str()
+        } else {
+            contents
+        };
+
+        // Bytes are always counted before comments are stripped and the lines
+        // are trimmed
+        let untrimmed = contents;
+        let stripped;
+        let contents = match self.config.comment_style {
+            Some(style) => {
+                stripped = strip_comments(contents, style);
+                stripped.as_str()
+            }
+            None => contents,
+        };
+        let trimmed;
+        let contents = if self.config.trim_whitespace {
+            trimmed = trim_lines(contents, char::from(self.config.line_separator));
+            trimmed.as_str()
+        } else {
+            contents
+        };
+
+        match self.config.count_chars {
+            CharCount::Chars => {
+                self.byte_or_char_count = Some(match self.config.locale {
+                    Locale::C => self.encoded_len(contents),
+                    Locale::Utf8 => count_characters(contents),
+                    Locale::Unicode => count_graphemes(contents),
+                });
+            }
+            CharCount::GraphemeClusters => self.byte_or_char_count = Some(count_graphemes(contents)),
+            CharCount::Bytes => {
+                self.byte_or_char_count = Some(self.encoded_len(untrimmed));
+            }
+            CharCount::None => self.byte_or_char_count = None,
+        }
+
+        if self.config.count_lines {
+            let separator = self.config.line_separator;
+            self.line_count = Some(if self.config.logical_lines {
+                count_logical_lines_separated_by(contents, separator)
+            } else {
+                count_lines_separated_by(contents, separator)
+            });
+        }
+
+        self.word_count = self.config.count_words.count(contents);
+
+        if self.config.count_max_line_length {
+            self.max_line_length = Some(max_line_length(contents));
+        }
+
+        if self.config.count_max_word_length {
+            self.max_word_length = Some(max_word_length(contents));
+        }
+
+        if self.config.count_line_stats {
+            self.line_lengths = Some(line_lengths(contents));
+        }
+
+        let folded;
+        let case_folded = if self.config.ignore_case
+            && (self.config.count_unique_words
+                || self.config.count_word_frequency
+                || self.config.count_unique_lines
+                || self.config.count_duplicate_lines)
+        {
+            folded = case_fold(contents);
+            folded.as_str()
+        } else {
+            contents
+        };
+
+        if self.config.count_unique_words {
+            self.unique_word_count = Some(count_unique_words(case_folded));
+        }
+
+        if self.config.count_word_frequency {
+            self.word_frequency = Some(word_frequency(case_folded));
+        }
+
+        match (self.config.count_unique_lines, self.config.count_duplicate_lines) {
+            (true, true) => {
+                // Both counts come from the same pass over the lines
+                let occurrences = line_occurrences(case_folded);
+                self.unique_line_count = Some(occurrences.len());
+                self.duplicate_line_count = Some(count_repeats(&occurrences));
+            }
+            (true, false) => self.unique_line_count = Some(count_unique_lines(case_folded)),
+            (false, true) => self.duplicate_line_count = Some(count_duplicate_lines(case_folded)),
+            (false, false) => {}
+        }
+
+        if self.config.count_paragraphs {
+            self.paragraph_count = Some(count_paragraphs(contents));
+        }
+
+        if self.config.count_non_blank_lines {
+            self.non_blank_line_count = Some(count_non_blank_lines(contents));
+        }
+
+        if self.config.count_tabs {
+            self.tab_count = Some(count_tabs(contents));
+        }
+
+        if self.config.count_spaces {
+            self.space_count = Some(count_spaces(contents));
+        }
+
+        if self.config.count_control_chars {
+            self.control_char_count = Some(if self.config.include_common_controls {
+                count_all_control_chars(contents.as_bytes())
+            } else {
+                count_control_chars(contents.as_bytes())
+            });
+        }
+
+        if self.config.count_non_ascii {
+            self.non_ascii_count = Some(if self.config.non_ascii_bytes {
+                count_non_ascii_bytes(contents.as_bytes())
+            } else {
+                count_non_ascii_chars(contents)
+            });
+        }
+
+        #[cfg(feature = "regex")]
+        if let Some(RegexPattern(pattern)) = &self.config.count_lines_matching {
+            self.matching_line_count = Some(count_lines_matching(contents, pattern));
+        }
+
+        if self.config.count_sentences {
+            self.sentence_count = Some(count_sentences(contents));
+        }
+
+        if self.config.count_average_word_length {
+            self.word_lengths = Some(word_lengths(contents));
+        }
+
+        if self.config.count_average_line_length {
+            self.line_char_lengths = Some(line_char_lengths(contents));
+        }
+
+        if let Some(buckets) = self.config.line_length_histogram {
+            let separator = self.config.line_separator;
+            self.line_length_histogram =
+                Some(line_length_histogram_separated_by(contents, buckets, separator));
+        }
+
+        self
+    }
+
+    /// Creates a CountResult holding the counts of the Counter and the
+    /// filename from its config, unless the config says to leave it out
+    pub fn result(&self) -> CountResult {
+        CountResult {
+            lines: self.line_count,
+            words: self.word_count,
+            bytes: match self.config.count_chars {
+                CharCount::Bytes => self.byte_or_char_count,
+                _ => None,
+            },
+            chars: match self.config.count_chars {
+                CharCount::Chars | CharCount::GraphemeClusters => self.byte_or_char_count,
+                _ => None,
+            },
+            max_line_length: self.max_line_length,
+            max_word_length: self.max_word_length,
+            line_stats: self.line_lengths.as_ref().map(LineLengths::stats),
+            unique_words: self.unique_word_count,
+            unique_lines: self.unique_line_count,
+            duplicate_lines: self.duplicate_line_count,
+            paragraphs: self.paragraph_count,
+            non_blank_lines: self.non_blank_line_count,
+            tabs: self.tab_count,
+            spaces: self.space_count,
+            control_chars: self.control_char_count,
+            non_ascii: self.non_ascii_count,
+            crlf: self.crlf_count,
+            matching_lines: self.matching_line_count,
+            sentences: self.sentence_count,
+            average_word_length: self.word_lengths.as_ref().map(LengthTotal::average),
+            average_line_length: self.line_char_lengths.as_ref().map(LengthTotal::average),
+            line_length_histogram: self.line_length_histogram.clone(),
+            encoding: self.detected_encoding.map(|encoding| encoding.name().to_owned()),
+            filename: if self.config.no_filename {
+                None
+            } else {
+                self.config.filename.clone()
+            },
+        }
+    }
+
+    /// Combines the counts of two Counters, such as those of two files, into
+    /// the counts of both together. As with the total the lengths are the
+    /// longest of the two and the unique counts are added, so are an upper
+    /// bound. The merged Counter has no filename. Returns an error if the
+    /// Counters were not made with configs that count the same things.
+    pub fn merge(self, other: Counter) -> Result<Counter, CcwcError> {
+        if !self.config.has_same_counts(&other.config) {
+            return Err(CcwcError::IncompatibleConfigs);
+        }
+
+        let mut config = self.config;
+        config.filename = None;
+
+        Ok(Counter {
+            config,
+            byte_or_char_count: add_counts(self.byte_or_char_count, other.byte_or_char_count),
+            word_count: add_counts(self.word_count, other.word_count),
+            line_count: add_counts(self.line_count, other.line_count),
+            max_line_length: combine(self.max_line_length, other.max_line_length, usize::max),
+            max_word_length: combine(self.max_word_length, other.max_word_length, usize::max),
+            line_lengths: combine(self.line_lengths, other.line_lengths, LineLengths::merge),
+            unique_word_count: add_counts(self.unique_word_count, other.unique_word_count),
+            word_frequency: combine(self.word_frequency, other.word_frequency, merge_frequencies),
+            unique_line_count: add_counts(self.unique_line_count, other.unique_line_count),
+            duplicate_line_count: add_counts(self.duplicate_line_count, other.duplicate_line_count),
+            paragraph_count: add_counts(self.paragraph_count, other.paragraph_count),
+            non_blank_line_count: add_counts(self.non_blank_line_count, other.non_blank_line_count),
+            tab_count: add_counts(self.tab_count, other.tab_count),
+            space_count: add_counts(self.space_count, other.space_count),
+            control_char_count: add_counts(self.control_char_count, other.control_char_count),
+            non_ascii_count: add_counts(self.non_ascii_count, other.non_ascii_count),
+            crlf_count: add_counts(self.crlf_count, other.crlf_count),
+            matching_line_count: add_counts(self.matching_line_count, other.matching_line_count),
+            sentence_count: add_counts(self.sentence_count, other.sentence_count),
+            word_lengths: combine(self.word_lengths, other.word_lengths, LengthTotal::merge),
+            line_char_lengths: combine(self.line_char_lengths, other.line_char_lengths, LengthTotal::merge),
+            line_length_histogram: combine(
+                self.line_length_histogram,
+                other.line_length_histogram,
+                Histogram::merge,
+            ),
+            detected_encoding: (self.detected_encoding == other.detected_encoding)
+                .then_some(self.detected_encoding)
+                .flatten(),
+        })
+    }
+}
+
+/// Formats the counts of the Counter in the same way as its CountResult
+impl fmt::Display for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.result())
+    }
+}
+
+/// The counts for a single input, or the totals across several inputs. Each
+/// count is `None` if it was not requested. At most one of `bytes` and `chars`
+/// is set since `-m` overrides `-c`. The filename is `None` when the input was
+/// read from stdin.
+///
+/// With the `serde` feature the CountResult can be serialized and
+/// deserialized:
+///
+/// ```
+/// # #[cfg(feature = "serde")]
+/// # {
+/// use ccwc::CountResult;
+///
+/// let result = CountResult {
+///     lines: Some(1),
+///     words: Some(2),
+///     bytes: Some(13),
+///     filename: Some("test.txt".to_owned()),
+///     ..Default::default()
+/// };
+///
+/// let json = serde_json::to_string(&result).unwrap();
+/// let deserialized: CountResult = serde_json::from_str(&json).unwrap();
+/// assert_eq!(deserialized, result);
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CountResult {
+    pub lines: Option<usize>,
+    pub words: Option<usize>,
+    pub bytes: Option<usize>,
+    pub chars: Option<usize>,
+    pub max_line_length: Option<usize>,
+    pub max_word_length: Option<usize>,
+    pub line_stats: Option<LineStats>,
+    pub unique_words: Option<usize>,
+    pub unique_lines: Option<usize>,
+    pub duplicate_lines: Option<usize>,
+    pub paragraphs: Option<usize>,
+    pub non_blank_lines: Option<usize>,
+    pub tabs: Option<usize>,
+    pub spaces: Option<usize>,
+    pub control_chars: Option<usize>,
+    pub non_ascii: Option<usize>,
+    pub crlf: Option<usize>,
+    pub matching_lines: Option<usize>,
+    pub sentences: Option<usize>,
+    pub average_word_length: Option<Average>,
+    pub average_line_length: Option<Average>,
+    /// Printed after the counts rather than in a column
+    pub line_length_histogram: Option<Histogram>,
+    /// The name of the encoding detected with `--encoding-detect`, which is
+    /// not printed in a column and is `None` for a total
+    pub encoding: Option<String>,
+    pub filename: Option<String>,
+}
+
+/// Statistics about the lengths in bytes of the lines in an input. The
+/// lengths do not include the line terminators. `lines` is the number of lines
+/// that the statistics are over, which unlike `-l` includes a final line with
+/// no trailing newline. All of the statistics are zero if there are no lines.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LineStats {
+    pub lines: usize,
+    pub min_line_bytes: usize,
+    pub max_line_bytes: usize,
+    pub mean_line_bytes: f64,
+}
+
+impl LineStats {
+    /// Combines the statistics of two inputs into the statistics of all of
+    /// their lines together
+    fn merge(self, other: LineStats) -> LineStats {
+        if self.lines == 0 {
+            return other;
+        }
+        if other.lines == 0 {
+            return self;
+        }
+
+        let lines = self.lines + other.lines;
+        LineStats {
+            lines,
+            min_line_bytes: self.min_line_bytes.min(other.min_line_bytes),
+            max_line_bytes: self.max_line_bytes.max(other.max_line_bytes),
+            mean_line_bytes: (self.mean_line_bytes * self.lines as f64
+                + other.mean_line_bytes * other.lines as f64)
+                / lines as f64,
+        }
+    }
+}
+
+/// The mean of a number of lengths, such as the lengths of the words in an
+/// input. `count` is the number of lengths that the mean is over, so that the
+/// means of several inputs can be combined. The mean is zero if there are no
+/// lengths.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Average {
+    pub count: usize,
+    pub mean: f64,
+}
+
+impl Average {
+    /// Combines the means of two sets of lengths into the mean of all of them
+    fn merge(self, other: Average) -> Average {
+        let count = self.count + other.count;
+        if count == 0 {
+            return Average::default();
+        }
+
+        Average {
+            count,
+            mean: (self.mean * self.count as f64 + other.mean * other.count as f64) / count as f64,
+        }
+    }
+}
+
+/// Formats the mean to two decimal places
+impl fmt::Display for Average {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2}", self.mean)
+    }
+}
+
+/// How many lines of an input have lengths in each of a number of ranges of
+/// the same size, starting from 0. The last range is open ended, so it
+/// includes every line that is too long for the others.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Histogram {
+    pub bucket_size: usize,
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    /// Combines the Histograms of two inputs into the Histogram of both
+    fn merge(mut self, other: Histogram) -> Histogram {
+        if other.counts.len() > self.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts) {
+            *count += other_count;
+        }
+        self
+    }
+}
+
+/// Formats the Histogram as a bar chart with one row per range, such as
+/// `10-19 | ###### 12`, where the longest bar is 40 characters
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let last = self.counts.len().saturating_sub(1);
+        let labels: Vec<String> = (0..self.counts.len())
+            .map(|i| {
+                let start = i * self.bucket_size;
+                if i == last {
+                    format!("{start}+")
+                } else {
+                    format!("{start}-{}", start + self.bucket_size - 1)
+                }
+            })
+            .collect();
+        let label_width = labels.iter().map(String::len).max().unwrap_or(0);
+        let most = self.counts.iter().copied().max().unwrap_or(0).max(1);
+
+        let rows: Vec<String> = labels
+            .iter()
+            .zip(&self.counts)
+            .map(|(label, &count)| {
+                let bar = "#".repeat((count * 40).div_ceil(most));
+                format!("{label:>label_width$} | {bar} {count}")
+            })
+            .collect();
+        write!(f, "{}", rows.join("\n"))
+    }
+}
+
+/// Adds the counts of two CountResults together, for example to find the
+/// totals across several inputs. Counts that are only in one of the results
+/// are kept as they are. The maximum line and word lengths are the larger of
+/// the two rather than their sum, and the line statistics and averages are
+/// those of all of the lines or words together. The numbers of unique words
+/// and lines are summed, so they are upper bounds on the numbers that are
+/// unique across both inputs. The filename of the sum is `None`.
+impl Add for CountResult {
+    type Output = CountResult;
+
+    fn add(self, other: CountResult) -> CountResult {
+        CountResult {
+            lines: add_counts(self.lines, other.lines),
+            words: add_counts(self.words, other.words),
+            bytes: add_counts(self.bytes, other.bytes),
+            chars: add_counts(self.chars, other.chars),
+            max_line_length: combine(self.max_line_length, other.max_line_length, usize::max),
+            max_word_length: combine(self.max_word_length, other.max_word_length, usize::max),
+            line_stats: combine(self.line_stats, other.line_stats, LineStats::merge),
+            unique_words: add_counts(self.unique_words, other.unique_words),
+            unique_lines: add_counts(self.unique_lines, other.unique_lines),
+            duplicate_lines: add_counts(self.duplicate_lines, other.duplicate_lines),
+            paragraphs: add_counts(self.paragraphs, other.paragraphs),
+            non_blank_lines: add_counts(self.non_blank_lines, other.non_blank_lines),
+            tabs: add_counts(self.tabs, other.tabs),
+            spaces: add_counts(self.spaces, other.spaces),
+            control_chars: add_counts(self.control_chars, other.control_chars),
+            non_ascii: add_counts(self.non_ascii, other.non_ascii),
+            crlf: add_counts(self.crlf, other.crlf),
+            matching_lines: add_counts(self.matching_lines, other.matching_lines),
+            sentences: add_counts(self.sentences, other.sentences),
+            average_word_length: combine(self.average_word_length, other.average_word_length, Average::merge),
+            average_line_length: combine(self.average_line_length, other.average_line_length, Average::merge),
+            line_length_histogram: combine(
+                self.line_length_histogram,
+                other.line_length_histogram,
+                Histogram::merge,
+            ),
+            encoding: None,
+            filename: None,
+        }
+    }
+}
+
+/// Adds up the counts of all of the CountResults. The sum of no results has
+/// every count set to `None`.
+impl Sum for CountResult {
+    fn sum<I: Iterator<Item = CountResult>>(iter: I) -> CountResult {
+        iter.fold(CountResult::default(), Add::add)
+    }
+}
+
+/// Counts the lines, words, bytes, and characters of a string, using the
+/// same rules as `count_lines`, `count_words`, `count_bytes`, and
+/// `count_characters`. Unlike the results of counting an input both `bytes`
+/// and `chars` are set. The other counts and the filename are `None`.
+///
+/// ```
+/// let result = ccwc::CountResult::from("hello world\nこんにちは\n");
+/// assert_eq!(result.lines, Some(2));
+/// assert_eq!(result.words, Some(3));
+/// assert_eq!(result.bytes, Some(28));
+/// assert_eq!(result.chars, Some(18));
+/// ```
+impl From<&str> for CountResult {
+    fn from(input_string: &str) -> CountResult {
+        CountResult {
+            lines: Some(count_lines(input_string)),
+            words: Some(count_words(input_string)),
+            bytes: Some(count_bytes(input_string)),
+            chars: Some(count_characters(input_string)),
+            ..Default::default()
+        }
+    }
+}
+
+/// Counts the lines, words, bytes, and characters of a string in the same
+/// way as `From<&str>`
+impl From<String> for CountResult {
+    fn from(input_string: String) -> CountResult {
+        CountResult::from(input_string.as_str())
+    }
+}
+
+/// The change in a single count between two inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountDelta {
+    pub before: usize,
+    pub after: usize,
+    /// How much the count went up by, negative if it went down
+    pub change: i64,
+}
+
+impl CountDelta {
+    /// Create the CountDelta between the two counts
+    fn new(before: usize, after: usize) -> CountDelta {
+        CountDelta { before, after, change: after as i64 - before as i64 }
+    }
+}
+
+/// Formats the CountDelta as `before -> after (change)`, e.g. `100 -> 150 (+50)`
+impl fmt::Display for CountDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {} ({:+})", self.before, self.after, self.change)
+    }
+}
+
+/// The changes in the counts from one input, the "before", to another, the
+/// "after". Each delta is `None` unless the count is in both of the results.
+/// The line statistics are not compared.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CountDiff {
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub lines: Option<CountDelta>,
+    pub words: Option<CountDelta>,
+    pub bytes: Option<CountDelta>,
+    pub chars: Option<CountDelta>,
+    pub max_line_length: Option<CountDelta>,
+    pub max_word_length: Option<CountDelta>,
+    pub unique_words: Option<CountDelta>,
+    pub unique_lines: Option<CountDelta>,
+    pub duplicate_lines: Option<CountDelta>,
+    pub paragraphs: Option<CountDelta>,
+    pub non_blank_lines: Option<CountDelta>,
+    pub tabs: Option<CountDelta>,
+    pub spaces: Option<CountDelta>,
+    pub control_chars: Option<CountDelta>,
+    pub non_ascii: Option<CountDelta>,
+    pub crlf: Option<CountDelta>,
+    pub matching_lines: Option<CountDelta>,
+    pub sentences: Option<CountDelta>,
+}
+
+/// Formats the CountDiff with the names of the before and after inputs on
+/// lines starting `---` and `+++` as in a unified diff, followed by a line
+/// such as `lines: 100 -> 150 (+50)` for each of the counts
+impl fmt::Display for CountDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "--- {}", self.before.as_deref().unwrap_or("before"))?;
+        write!(f, "+++ {}", self.after.as_deref().unwrap_or("after"))?;
+
+        for (name, delta) in [
+            ("lines", self.lines),
+            ("words", self.words),
+            ("bytes", self.bytes),
+            ("chars", self.chars),
+            ("max_line_length", self.max_line_length),
+            ("max_word_length", self.max_word_length),
+            ("unique_words", self.unique_words),
+            ("unique_lines", self.unique_lines),
+            ("duplicate_lines", self.duplicate_lines),
+            ("paragraphs", self.paragraphs),
+            ("non_blank_lines", self.non_blank_lines),
+            ("tabs", self.tabs),
+            ("spaces", self.spaces),
+            ("control_chars", self.control_chars),
+            ("non_ascii", self.non_ascii),
+            ("crlf", self.crlf),
+            ("matching_lines", self.matching_lines),
+            ("sentences", self.sentences),
+        ] {
+            if let Some(delta) = delta {
+                write!(f, "\n{name}: {delta}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares the counts of two results, where `a` is the before and `b` is
+/// the after
+///
+/// ```
+/// let before = ccwc::CountResult { lines: Some(100), ..Default::default() };
+/// let after = ccwc::CountResult { lines: Some(150), ..Default::default() };
+///
+/// let diff = ccwc::diff_results(&before, &after);
+/// assert_eq!(diff.lines.unwrap().change, 50);
+/// assert_eq!(diff.words, None);
+/// ```
+pub fn diff_results(a: &CountResult, b: &CountResult) -> CountDiff {
+    let delta = |before: Option<usize>, after: Option<usize>| {
+        Some(CountDelta::new(before?, after?))
+    };
+
+    CountDiff {
+        before: a.filename.clone(),
+        after: b.filename.clone(),
+        lines: delta(a.lines, b.lines),
+        words: delta(a.words, b.words),
+        bytes: delta(a.bytes, b.bytes),
+        chars: delta(a.chars, b.chars),
+        max_line_length: delta(a.max_line_length, b.max_line_length),
+        max_word_length: delta(a.max_word_length, b.max_word_length),
+        unique_words: delta(a.unique_words, b.unique_words),
+        unique_lines: delta(a.unique_lines, b.unique_lines),
+        duplicate_lines: delta(a.duplicate_lines, b.duplicate_lines),
+        paragraphs: delta(a.paragraphs, b.paragraphs),
+        non_blank_lines: delta(a.non_blank_lines, b.non_blank_lines),
+        tabs: delta(a.tabs, b.tabs),
+        spaces: delta(a.spaces, b.spaces),
+        control_chars: delta(a.control_chars, b.control_chars),
+        non_ascii: delta(a.non_ascii, b.non_ascii),
+        crlf: delta(a.crlf, b.crlf),
+        matching_lines: delta(a.matching_lines, b.matching_lines),
+        sentences: delta(a.sentences, b.sentences),
+    }
+}
+
+/// Options for how the counts are formatted when they are printed in columns
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    byte_units: Option<ByteUnits>,
+    include_zero: bool,
+    column_separator: String,
+    color: bool,
+    header: bool,
+}
+
+impl FormatOptions {
+    /// Create the FormatOptions from the given cli options
+    pub fn from_cli(cli: &Cli) -> FormatOptions {
+        FormatOptions {
+            byte_units: if cli.human_readable {
+                Some(ByteUnits::Binary)
+            } else if cli.si {
+                Some(ByteUnits::Si)
+            } else {
+                None
+            },
+            include_zero: cli.include_zero,
+            column_separator: if cli.tabulate {
+                "\t".to_owned()
+            } else if cli.number {
+                " ".to_owned()
+            } else {
+                cli.column_separator.clone()
+            },
+            color: match cli.color {
+                _ if cli.no_color => false,
+                ColorMode::Auto => cli.writes_to_stdout() && io::stdout().is_terminal(),
+                ColorMode::Always => true,
+                ColorMode::Never => false,
+            },
+            header: cli.header && !cli.omit_header,
+        }
+    }
+
+    /// Formats a byte or character count, in human readable units if they
+    /// have been requested
+    fn format_bytes(&self, count: usize) -> String {
+        match self.byte_units {
+            Some(ByteUnits::Binary) => humanize_bytes(count, true),
+            Some(ByteUnits::Si) => humanize_bytes(count, false),
+            None => count.to_string(),
+        }
+    }
+}
+
+/// The units used for human readable byte counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteUnits {
+    /// Powers of 1000: kB, MB, GB, TB
+    Si,
+    /// Powers of 1024: KiB, MiB, GiB, TiB
+    Binary,
+}
+
+/// Formats a number of bytes with one decimal place in the largest unit that
+/// it is at least one of, e.g. `1.5KiB`. Uses powers of 1024 if `binary` is
+/// true and powers of 1000 otherwise. Counts smaller than one kilobyte are
+/// printed as they are.
+///
+/// ```
+/// assert_eq!(ccwc::humanize_bytes(1023, true), "1023");
+/// assert_eq!(ccwc::humanize_bytes(1536, true), "1.5KiB");
+/// assert_eq!(ccwc::humanize_bytes(1_000_000, false), "1.0MB");
+/// ```
+pub fn humanize_bytes(n: usize, binary: bool) -> String {
+    let (base, units) = if binary {
+        (1024.0, ["KiB", "MiB", "GiB", "TiB"])
+    } else {
+        (1000.0, ["kB", "MB", "GB", "TB"])
+    };
+
+    let mut value = n as f64;
+    if value < base {
+        return n.to_string();
+    }
+
+    let mut unit = 0;
+    value /= base;
+    // Move up a unit if the value would otherwise be rounded up to `base`
+    while (value * 10.0).round() / 10.0 >= base && unit < units.len() - 1 {
+        value /= base;
+        unit += 1;
+    }
+
+    format!("{:.1}{}", value, units[unit])
+}
+
+/// The value of one of the counts of a CountResult, which is a whole number
+/// except for the means of lengths
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CountValue {
+    Whole(usize),
+    Mean(f64),
+}
+
+impl CountValue {
+    /// Returns true if the count is less than the bound
+    fn is_below(self, bound: usize) -> bool {
+        match self {
+            CountValue::Whole(count) => count < bound,
+            CountValue::Mean(mean) => mean < bound as f64,
+        }
+    }
+
+    /// Returns true if the count is more than the bound
+    fn is_above(self, bound: usize) -> bool {
+        match self {
+            CountValue::Whole(count) => count > bound,
+            CountValue::Mean(mean) => mean > bound as f64,
+        }
+    }
+}
+
+/// Formats means to two decimal places, as they are in the output
+impl fmt::Display for CountValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CountValue::Whole(count) => write!(f, "{count}"),
+            CountValue::Mean(mean) => write!(f, "{mean:.2}"),
+        }
+    }
+}
+
+impl CountResult {
+    /// The names and values of the counts that were made, in the order of
+    /// the output columns. The names are those used in the CSV header.
+    fn named_counts(&self) -> Vec<(&'static str, CountValue)> {
+        let mut counts = Vec::new();
+        for (name, count) in [
+            ("lines", self.lines),
+            ("words", self.words),
+            ("chars", self.chars),
+            ("bytes", if self.chars.is_some() { None } else { self.bytes }),
+            ("max_line_length", self.max_line_length),
+            ("max_word_length", self.max_word_length),
+        ] {
+            if let Some(count) = count {
+                counts.push((name, CountValue::Whole(count)));
+            }
+        }
+        if let Some(line_stats) = &self.line_stats {
+            counts.push(("min_line_bytes", CountValue::Whole(line_stats.min_line_bytes)));
+            counts.push(("max_line_bytes", CountValue::Whole(line_stats.max_line_bytes)));
+            counts.push(("mean_line_bytes", CountValue::Mean(line_stats.mean_line_bytes)));
+        }
+        for (name, count) in [
+            ("unique_words", self.unique_words),
+            ("unique_lines", self.unique_lines),
+            ("duplicate_lines", self.duplicate_lines),
+            ("paragraphs", self.paragraphs),
+            ("non_blank_lines", self.non_blank_lines),
+            ("tabs", self.tabs),
+            ("spaces", self.spaces),
+            ("control_chars", self.control_chars),
+            ("non_ascii", self.non_ascii),
+            ("crlf", self.crlf),
+            ("matching_lines", self.matching_lines),
+            ("sentences", self.sentences),
+        ] {
+            if let Some(count) = count {
+                counts.push((name, CountValue::Whole(count)));
+            }
+        }
+        for (name, average) in [
+            ("average_word_length", self.average_word_length),
+            ("average_line_length", self.average_line_length),
+        ] {
+            if let Some(average) = average {
+                counts.push((name, CountValue::Mean(average.mean)));
+            }
+        }
+
+        counts
+    }
+
+    /// The names and formatted values of the output columns in order, not
+    /// including the filename. The names are those used in the CSV header.
+    fn named_columns(&self, options: &FormatOptions) -> Vec<(&'static str, String)> {
+        let mut counts = self.named_counts();
+        if options.include_zero {
+            // The line, word, byte, and character columns are all printed
+            // even if they were not counted
+            counts.retain(|(name, _)| !matches!(*name, "lines" | "words" | "chars" | "bytes"));
+            let zero = |count: Option<usize>| CountValue::Whole(count.unwrap_or(0));
+            let first = [
+                ("lines", zero(self.lines)),
+                ("words", zero(self.words)),
+                ("bytes", zero(self.bytes)),
+                ("chars", zero(self.chars)),
+            ];
+            counts.splice(0..0, first);
+        }
+
+        counts
+            .into_iter()
+            .map(|(name, count)| match (name, count) {
+                ("bytes" | "chars", CountValue::Whole(count)) => (name, options.format_bytes(count)),
+                _ => (name, count.to_string()),
+            })
+            .collect()
+    }
+
+    /// The formatted values of the output columns in order, not including
+    /// the filename
+    fn columns(&self, options: &FormatOptions) -> Vec<String> {
+        self.named_columns(options).into_iter().map(|(_, column)| column).collect()
+    }
+
+    /// The colors of the line, word, and byte or character count columns,
+    /// which come before all of the other columns
+    fn count_colors(&self, options: &FormatOptions) -> Vec<Color> {
+        if options.include_zero {
+            return vec![Color::Blue, Color::Green, Color::Yellow, Color::Yellow];
+        }
+
+        let mut colors = Vec::new();
+        if self.lines.is_some() {
+            colors.push(Color::Blue);
+        }
+        if self.words.is_some() {
+            colors.push(Color::Green);
+        }
+        if self.chars.or(self.bytes).is_some() {
+            colors.push(Color::Yellow);
+        }
+        colors
+    }
+
+    /// Formats the CountResult in the same way as `format_row`, but with ANSI
+    /// escape codes coloring the line counts blue, the word counts green, the
+    /// byte or character counts yellow, and the filename bold. Every column is
+    /// bold if `bold` is true.
+    fn format_colored_row(&self, column_width: usize, options: &FormatOptions, bold: bool) -> String {
+        let mut buffer = Buffer::ansi();
+        let mut colors = self.count_colors(options).into_iter();
+
+        for column in self.columns(options) {
+            let mut spec = ColorSpec::new();
+            spec.set_fg(colors.next()).set_bold(bold);
+            write_colored(&mut buffer, &spec, &format_output(&column, column_width));
+        }
+
+        if let Some(filename) = &self.filename {
+            write_colored(&mut buffer, ColorSpec::new().set_bold(true), &format!(" {filename}"));
+        }
+
+        String::from_utf8(buffer.into_inner()).expect("only UTF-8 is written to the buffer")
+    }
+
+    /// Formats the CountResult with every column right-aligned in the given
+    /// width, followed by the filename if there is one. If the options have a
+    /// column separator then the columns are not aligned but separated by it.
+    fn format_row(&self, column_width: usize, options: &FormatOptions) -> String {
+        if !options.column_separator.is_empty() {
+            let mut fields = self.columns(options);
+            fields.extend(self.filename.clone());
+            return fields.join(&options.column_separator);
+        }
+
+        let mut output = String::new();
+
+        for column in self.columns(options) {
+            output.push_str(&format_output(&column, column_width));
+        }
+
+        if let Some(filename) = &self.filename {
+            output.push_str(&format!(" {}", filename));
+        }
+
+        output
+    }
+}
+
+/// Formats the CountResult as follows:
+/// line_count word_count byte_count max_line_length max_word_length
+/// min_line_bytes max_line_bytes mean_line_bytes unique_words unique_lines
+/// duplicate_lines paragraphs non_blank_lines tabs spaces control_chars
+/// non_ascii crlf matching_lines sentences average_word_length
+/// average_line_length filename
+/// where each count is right-aligned in a column whose width is the smallest
+/// multiple of 4 wider than the widest count
+impl fmt::Display for CountResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let options = FormatOptions::default();
+        let column_width = column_width(std::slice::from_ref(self), &options);
+        write!(f, "{}", self.format_row(column_width, &options))
+    }
+}
+
+/// Formats each of the results in the same way as their Display
+/// implementation, apart from any changes made by the options, but with a
+/// single column width for all of them so that the columns line up in the same
+/// way as GNU `wc`
+pub fn format_all(results: &[CountResult], options: &FormatOptions) -> Vec<String> {
+    let column_width = column_width(results, options);
+    if !options.color || !options.column_separator.is_empty() {
+        return results.iter().map(|result| result.format_row(column_width, options)).collect();
+    }
+
+    results
+        .iter()
+        .enumerate()
+        .map(|(index, result)| {
+            let is_total = index + 1 == results.len() && result.filename.as_deref() == Some("total");
+            result.format_colored_row(column_width, options, is_total)
+        })
+        .collect()
+}
+
+/// Writes the text to the buffer in the style of the spec, and then resets
+/// the style
+fn write_colored(buffer: &mut Buffer, spec: &ColorSpec, text: &str) {
+    // Writing to an in memory buffer cannot fail
+    let _ = buffer.set_color(spec);
+    let _ = buffer.write_all(text.as_bytes());
+    let _ = buffer.reset();
+}
+
+/// Formats a row naming each of the columns of the results, such as
+/// `   lines   words   bytes filename`, aligned with the rows of `format_all`.
+/// The columns are named as in the CSV header. Returns `None` if there are no
+/// results.
+pub fn format_header(results: &[CountResult], options: &FormatOptions) -> Option<String> {
+    let first = results.first()?;
+    let mut names: Vec<&str> =
+        first.named_columns(options).into_iter().map(|(name, _)| name).collect();
+    let has_filename = results.iter().any(|result| result.filename.is_some());
+
+    if !options.column_separator.is_empty() {
+        names.extend(has_filename.then_some("filename"));
+        return Some(names.join(&options.column_separator));
+    }
+
+    let column_width = column_width(results, options);
+    let mut header: String = names.iter().map(|name| format_output(name, column_width)).collect();
+    if has_filename {
+        header.push_str(" filename");
+    }
+    Some(header)
+}
+
+/// Finds the smallest multiple of 4 that is wider than every column of the
+/// given results, and every column name if the options include a header
+fn column_width(results: &[CountResult], options: &FormatOptions) -> usize {
+    let widest = results
+        .iter()
+        .flat_map(|result| result.named_columns(options))
+        .map(|(name, column)| if options.header { column.len().max(name.len()) } else { column.len() })
+        .max()
+        .unwrap_or(0);
+    4 * ((widest / 4) + 1)
+}
+
+/// Formats a string so that it is right-aligned in a column of the given width
+fn format_output(input_string: &str, column_width: usize) -> String {
+    format!("{input_string: >column_width$}", column_width=column_width)
+}
+
+/// Count the number of bytes in a string
+///
+/// ```
+/// assert_eq!(ccwc::count_bytes("Hello, world!"), 13);
+/// assert_eq!(ccwc::count_bytes("こんにちは"), 15);
+/// ```
+pub fn count_bytes(input_string: &str) -> usize {
+    input_string.len()
+}
+
+/// Count the number of bytes in raw input, before it is decoded. Unlike
+/// `count_bytes` this does not depend on the input being UTF-8, so it is the
+/// size of an input in any encoding.
+///
+/// ```
+/// assert_eq!(ccwc::count_bytes_raw(b"caf\xe9"), 4);
+/// assert_eq!(ccwc::count_bytes_raw("こんにちは".as_bytes()), 15);
+/// ```
+pub fn count_bytes_raw(data: &[u8]) -> usize {
+    data.len()
+}
+
+/// Count the number of characters in a string
+///
+/// ```
+/// assert_eq!(ccwc::count_characters("Hello, world!"), 13);
+/// assert_eq!(ccwc::count_characters("こんにちは"), 5);
+/// ```
+pub fn count_characters(input_string: &str) -> usize {
+    input_string.chars().count()
+}
+
+/// Count the number of extended grapheme clusters in a string, which is the
+/// number of characters that a reader would see. For example, an `e` followed
+/// by a combining acute accent is two `char`s but one grapheme cluster.
+///
+/// ```
+/// assert_eq!(ccwc::count_graphemes("e\u{301}t\u{e9}"), 3);
+/// assert_eq!(ccwc::count_graphemes("👍🏽"), 1);
+/// ```
+pub fn count_graphemes(input_string: &str) -> usize {
+    input_string.graphemes(true).count()
+}
+
+/// Count the number of lines in a string. As with POSIX `wc -l` this is the
+/// number of newline characters, so a final line without a trailing newline is
+/// not counted.
+///
+/// ```
+/// assert_eq!(ccwc::count_lines("Hello\nworld\n"), 2);
+/// assert_eq!(ccwc::count_lines("Hello\nworld"), 1);
+/// ```
+pub fn count_lines(input_string: &str) -> usize {
+    count_lines_separated_by(input_string, b'\n')
+}
+
+/// Count the number of lines in a string where lines are terminated by the
+/// given separator byte rather than a newline character
+fn count_lines_separated_by(input_string: &str, separator: u8) -> usize {
+    input_string.bytes().filter(|&b| b == separator).count()
+}
+
+/// Count the number of logical lines in a string, as `str::lines` does. Unlike
+/// `count_lines`, which counts newline characters as POSIX `wc -l` does, a
+/// final line without a trailing newline is counted as a line.
+///
+/// ```
+/// assert_eq!(ccwc::count_logical_lines("Hello\nworld\n"), 2);
+/// assert_eq!(ccwc::count_logical_lines("Hello\nworld"), 2);
+/// assert_eq!(ccwc::count_logical_lines(""), 0);
+/// ```
+pub fn count_logical_lines(input_string: &str) -> usize {
+    count_logical_lines_separated_by(input_string, b'\n')
+}
+
+/// Count the number of logical lines in a string where lines are separated
+/// by the given separator byte rather than a newline character
+fn count_logical_lines_separated_by(input_string: &str, separator: u8) -> usize {
+    let unterminated = input_string.bytes().last().is_some_and(|b| b != separator);
+    count_lines_separated_by(input_string, separator) + usize::from(unterminated)
+}
+
+/// Count the number of words in a string. Words are separated by any Unicode
+/// whitespace character (see `char::is_whitespace`), so non-ASCII whitespace
+/// such as U+00A0 NO-BREAK SPACE or U+2003 EM SPACE separates words too. This
+/// differs from GNU `wc` in the C locale, which only splits on ASCII
+/// whitespace, but agrees with it in a UTF-8 locale.
+///
+/// ```
+/// assert_eq!(ccwc::count_words("This is a sentence."), 4);
+/// assert_eq!(ccwc::count_words("no-break\u{a0}space"), 2);
+/// ```
+pub fn count_words(input_string: &str) -> usize {
+    count_tokens(input_string, |s| Box::new(s.split_whitespace()))
+}
+
+/// Count the number of tokens in a string, where the tokenizer splits the
+/// string into its tokens. `count_words` is this with a tokenizer that splits
+/// on whitespace, and others can count domain-specific tokens such as the
+/// identifiers in source code.
+///
+/// ```
+/// fn identifiers(s: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+///     Box::new(s.split(|c: char| !c.is_alphanumeric() && c != '_').filter(|t| !t.is_empty()))
+/// }
+/// assert_eq!(ccwc::count_tokens("let x_1 = f(y);", identifiers), 4);
+/// assert_eq!(ccwc::count_tokens("a,b,,c", |s| Box::new(s.split(','))), 4);
+/// ```
+pub fn count_tokens<'a, F>(input: &'a str, tokenizer: F) -> usize
+where
+    F: Fn(&'a str) -> Box<dyn Iterator<Item = &'a str> + 'a>,
+{
+    tokenizer(input).count()
+}
+
+/// An iterator over the words of a string, split in the same way as
+/// `count_words`, created by `words`
+#[derive(Debug, Clone)]
+pub struct WordIterator<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Iterator for WordIterator<'a> {
+    /// The byte offset of the start of the word, the byte offset just past
+    /// the end of the word, and the word itself
+    type Item = (usize, usize, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.input[self.position..];
+        let start = self.position + rest.find(|c: char| !c.is_whitespace())?;
+        let end = self.input[start..]
+            .find(char::is_whitespace)
+            .map_or(self.input.len(), |length| start + length);
+
+        self.position = end;
+        Some((start, end, &self.input[start..end]))
+    }
+}
+
+/// Iterate over the words of a string along with their byte offsets. Words
+/// are separated by whitespace as for `count_words`.
+///
+/// ```
+/// let words: Vec<_> = ccwc::words("Hello,  world!").collect();
+/// assert_eq!(words, vec![(0, 6, "Hello,"), (8, 14, "world!")]);
+/// ```
+pub fn words(input_string: &str) -> WordIterator<'_> {
+    WordIterator { input: input_string, position: 0 }
+}
+
+/// An iterator over the lines of a string that keeps each line's terminator,
+/// created by `lines_with_terminators`
+#[derive(Debug, Clone)]
+pub struct LineIterator<'a> {
+    input: &'a str,
+    line_number: usize,
+}
+
+impl<'a> Iterator for LineIterator<'a> {
+    /// The 1-based line number and the line including its `\n` or `\r\n`
+    /// terminator, if it has one
+    type Item = (usize, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.input.is_empty() {
+            return None;
+        }
+
+        let end = self.input.find('\n').map_or(self.input.len(), |i| i + 1);
+        let (line, rest) = self.input.split_at(end);
+        self.input = rest;
+        self.line_number += 1;
+
+        Some((self.line_number, line))
+    }
+}
+
+/// Iterate over the lines of a string along with their 1-based line numbers.
+/// Unlike `str::lines` the `\n` or `\r\n` terminator is kept at the end of
+/// each line, so a final line without a terminator can be told apart.
+///
+/// ```
+/// let lines: Vec<_> = ccwc::lines_with_terminators("one\r\ntwo\nthree").collect();
+/// assert_eq!(lines, vec![(1, "one\r\n"), (2, "two\n"), (3, "three")]);
+/// ```
+pub fn lines_with_terminators(input_string: &str) -> LineIterator<'_> {
+    LineIterator { input: input_string, line_number: 0 }
+}
+
+/// Count how many times each word occurs in a string. Words are split in the
+/// same way as for `count_words`.
+///
+/// ```
+/// let frequency = ccwc::word_frequency("the cat and the hat");
+/// assert_eq!(frequency["the"], 2);
+/// assert_eq!(frequency["cat"], 1);
+/// ```
+pub fn word_frequency(input_string: &str) -> HashMap<String, usize> {
+    let mut frequency = HashMap::new();
+    for word in input_string.split_whitespace() {
+        *frequency.entry(word.to_owned()).or_insert(0) += 1;
+    }
+    frequency
+}
+
+/// Adds the word frequencies of two inputs together
+fn merge_frequencies(
+    mut a: HashMap<String, usize>,
+    b: HashMap<String, usize>,
+) -> HashMap<String, usize> {
+    for (word, count) in b {
+        *a.entry(word).or_insert(0) += count;
+    }
+    a
+}
+
+/// Formats the word frequencies one per line as `<count>\t<word>`, sorted by
+/// count with the most frequent first and ties in alphabetical order. Only the
+/// first `top` words are included if it is given.
+fn format_frequency(frequency: HashMap<String, usize>, top: Option<usize>) -> String {
+    let mut entries: Vec<(String, usize)> = frequency.into_iter().collect();
+    entries.sort_by(|(a_word, a_count), (b_word, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_word.cmp(b_word))
+    });
+
+    entries
+        .into_iter()
+        .take(top.unwrap_or(usize::MAX))
+        .map(|(word, count)| format!("{count}\t{word}"))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Applies full Unicode case folding to a string so that strings which only
+/// differ in case become equal. This allocates a new string of about the same
+/// size as the input.
+fn case_fold(input_string: &str) -> String {
+    input_string.case_fold().collect()
+}
+
+/// Replace every Windows `\r\n` line ending in a string with `\n`. A `\r`
+/// that is not followed by `\n` is left as it is.
+fn normalize_crlf(input_string: &str) -> String {
+    input_string.replace("\r\n", "\n")
+}
+
+/// Where `strip_comments` is in its input
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CommentState {
+    Code,
+    /// In a string quoted by the character
+    Quoted(char),
+    /// After a backslash in a string quoted by the character
+    Escaped(char),
+    LineComment,
+    BlockComment,
+}
+
+/// Removes the comments in the given style from a string, leaving any comment
+/// markers inside quoted strings alone. A block comment is replaced by a
+/// space, as a C compiler does. Lines that are blank once their comments are
+/// removed are left out, but other blank lines are kept.
+///
+/// ```
+/// use ccwc::CommentStyle;
+///
+/// let code = "// setup\nlet url = \"http://a\"; // why\n\nrun();\n";
+/// let stripped = ccwc::strip_comments(code, CommentStyle::DoubleSlash);
+/// assert_eq!(stripped, "let url = \"http://a\"; \n\nrun();\n");
+/// ```
+pub fn strip_comments(input: &str, style: CommentStyle) -> String {
+    let mut stripped = String::with_capacity(input.len());
+    let mut line = String::new();
+    let mut state = CommentState::Code;
+    let mut line_had_comment = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            line.push(c);
+            if !(line_had_comment && line.trim().is_empty()) {
+                stripped.push_str(&line);
+            }
+            line.clear();
+            // Only block comments, and not strings, carry on to the next line
+            if state != CommentState::BlockComment {
+                state = CommentState::Code;
+            }
+            line_had_comment = state == CommentState::BlockComment;
+            continue;
+        }
+
+        state = match state {
+            CommentState::Code => {
+                let next = chars.peek().copied();
+                let comment = match (style, c, next) {
+                    (CommentStyle::DoubleSlash, '/', Some('/'))
+                    | (CommentStyle::DoubleDash, '-', Some('-')) => Some(CommentState::LineComment),
+                    (CommentStyle::Hash, '#', _) => Some(CommentState::LineComment),
+                    (CommentStyle::Block, '/', Some('*')) => Some(CommentState::BlockComment),
+                    _ => None,
+                };
+                match comment {
+                    Some(comment) => {
+                        if style != CommentStyle::Hash {
+                            chars.next();
+                        }
+                        line_had_comment = true;
+                        comment
+                    }
+                    None => {
+                        line.push(c);
+                        if style.quotes().contains(&c) {
+                            CommentState::Quoted(c)
+                        } else {
+                            CommentState::Code
+                        }
+                    }
+                }
+            }
+            CommentState::Quoted(quote) => {
+                line.push(c);
+                match c {
+                    '\\' => CommentState::Escaped(quote),
+                    _ if c == quote => CommentState::Code,
+                    _ => CommentState::Quoted(quote),
+                }
+            }
+            CommentState::Escaped(quote) => {
+                line.push(c);
+                CommentState::Quoted(quote)
+            }
+            CommentState::LineComment => CommentState::LineComment,
+            CommentState::BlockComment => {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    line.push(' ');
+                    CommentState::Code
+                } else {
+                    CommentState::BlockComment
+                }
+            }
+        };
+    }
+
+    if !(line_had_comment && line.trim().is_empty()) {
+        stripped.push_str(&line);
+    }
+    stripped
+}
+
+/// Removes the whitespace from the start and end of each line in a string,
+/// keeping the separators between the lines
+fn trim_lines(input_string: &str, separator: char) -> String {
+    input_string
+        .split(separator)
+        .map(str::trim)
+        .collect::<Vec<&str>>()
+        .join(&separator.to_string())
+}
+
+/// Find the length in bytes of the longest line in a string
+fn max_line_length(input_string: &str) -> usize {
+    input_string.lines().map(str::len).max().unwrap_or(0)
+}
+
+/// Find the length in characters of the longest word in a string, where words
+/// are split in the same way as for `count_words`
+fn max_word_length(input_string: &str) -> usize {
+    input_string
+        .split_whitespace()
+        .map(|word| word.chars().count())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Count the number of distinct words in a string, where words are split in
+/// the same way as for `count_words`
+fn count_unique_words(input_string: &str) -> usize {
+    input_string.split_whitespace().collect::<HashSet<&str>>().len()
+}
+
+/// Count the number of distinct lines in a string, where lines are split in
+/// the same way as for `max_line_length`. A reference to every distinct line
+/// is kept, so this uses memory proportional to the number of lines.
+fn count_unique_lines(input_string: &str) -> usize {
+    input_string.lines().collect::<HashSet<&str>>().len()
+}
+
+/// Count the number of lines in a string that are exact repeats of an
+/// earlier line, where lines are split in the same way as for
+/// `max_line_length`. A line that occurs `n` times is counted `n - 1` times.
+///
+/// ```
+/// assert_eq!(ccwc::count_duplicate_lines("a\nb\na\na\n"), 2);
+/// assert_eq!(ccwc::count_duplicate_lines("a\nb\n"), 0);
+/// ```
+pub fn count_duplicate_lines(input_string: &str) -> usize {
+    count_repeats(&line_occurrences(input_string))
+}
+
+/// How many times each distinct line occurs in a string, where lines are
+/// split in the same way as for `max_line_length`
+fn line_occurrences(input_string: &str) -> HashMap<&str, usize> {
+    let mut occurrences = HashMap::new();
+    for line in input_string.lines() {
+        *occurrences.entry(line).or_insert(0) += 1;
+    }
+    occurrences
+}
+
+/// The number of occurrences beyond the first of each of the lines
+fn count_repeats(occurrences: &HashMap<&str, usize>) -> usize {
+    occurrences.values().map(|count| count - 1).sum()
+}
+
+/// Count the number of paragraphs in a string. A paragraph starts at each
+/// line that is not blank and follows either a blank line or the start of the
+/// string. Lines that only contain whitespace are blank.
+fn count_paragraphs(input_string: &str) -> usize {
+    let mut paragraphs = 0;
+    let mut previous_blank = true;
+
+    for line in input_string.lines() {
+        let blank = line.trim().is_empty();
+        if previous_blank && !blank {
+            paragraphs += 1;
+        }
+        previous_blank = blank;
+    }
+
+    paragraphs
+}
+
+/// Count the number of tab characters in a string
+fn count_tabs(input_string: &str) -> usize {
+    input_string.bytes().filter(|&b| b == b'\t').count()
+}
+
+/// Count the number of ASCII space characters in a string
+fn count_spaces(input_string: &str) -> usize {
+    input_string.bytes().filter(|&b| b == b' ').count()
+}
+
+/// Count the number of ASCII control characters, bytes 0x00 to 0x1F and 0x7F,
+/// in the input apart from tabs, newlines, and carriage returns, which are
+/// expected in text
+///
+/// ```
+/// assert_eq!(ccwc::count_control_chars(b"one\ttwo\r\n"), 0);
+/// assert_eq!(ccwc::count_control_chars(b"\x1b[1mbold\x1b[0m\x7f\0"), 4);
+/// ```
+pub fn count_control_chars(input: &[u8]) -> usize {
+    input
+        .iter()
+        .filter(|&&b| is_control_char(b) && !matches!(b, b'\t' | b'\n' | b'\r'))
+        .count()
+}
+
+/// Count the number of ASCII control characters in the input, including
+/// tabs, newlines, and carriage returns
+fn count_all_control_chars(input: &[u8]) -> usize {
+    input.iter().filter(|&&b| is_control_char(b)).count()
+}
+
+/// Returns true if the byte is an ASCII control character
+fn is_control_char(b: u8) -> bool {
+    b < 0x20 || b == 0x7f
+}
+
+/// Count the number of Windows `\r\n` line endings in the input. A `\r` that
+/// is not followed by `\n` is not counted.
+///
+/// ```
+/// assert_eq!(ccwc::count_crlf(b"one\r\ntwo\nthree\r\n"), 2);
+/// assert_eq!(ccwc::count_crlf(b"\r\r\n\n\r"), 1);
+/// ```
+pub fn count_crlf(input: &[u8]) -> usize {
+    input.windows(2).filter(|pair| pair == b"\r\n").count()
+}
+
+/// Count the number of bytes in the input that are not ASCII, which are those
+/// greater than 127
+///
+/// ```
+/// assert_eq!(ccwc::count_non_ascii_bytes(b"plain"), 0);
+/// assert_eq!(ccwc::count_non_ascii_bytes("caf\u{e9}".as_bytes()), 2);
+/// ```
+pub fn count_non_ascii_bytes(input: &[u8]) -> usize {
+    input.iter().filter(|b| !b.is_ascii()).count()
+}
+
+/// Count the number of characters in a string that are not ASCII, which are
+/// those greater than U+007F
+///
+/// ```
+/// assert_eq!(ccwc::count_non_ascii_chars("plain"), 0);
+/// assert_eq!(ccwc::count_non_ascii_chars("caf\u{e9}"), 1);
+/// ```
+pub fn count_non_ascii_chars(input_string: &str) -> usize {
+    input_string.chars().filter(|c| !c.is_ascii()).count()
+}
+
+/// Count the number of lines in a string that match the pattern anywhere in
+/// the line, where lines are split in the same way as for `max_line_length`
+#[cfg(feature = "regex")]
+fn count_lines_matching(input_string: &str, pattern: &Regex) -> usize {
+    input_string.lines().filter(|line| pattern.is_match(line)).count()
+}
+
+/// Count the number of non-overlapping matches of the pattern in a string,
+/// which are the words for `--word-regex`
+#[cfg(feature = "regex")]
+fn count_words_by_regex(input_string: &str, pattern: &Regex) -> usize {
+    pattern.find_iter(input_string).count()
+}
+
+/// Count the number of fields in a string, where each line, split in the same
+/// way as for `max_line_length`, is split into fields by the delimiter. An
+/// empty line has one empty field.
+fn count_fields(input_string: &str, delimiter: char) -> usize {
+    input_string.lines().map(|line| line.split(delimiter).count()).sum()
+}
+
+/// Count the number of lines in a string that are not blank, where lines are
+/// split in the same way as for `max_line_length`. Lines that only contain
+/// whitespace are blank.
+fn count_non_blank_lines(input_string: &str) -> usize {
+    input_string.lines().filter(|line| !line.trim().is_empty()).count()
+}
+
+/// Abbreviations that are usually followed by a full stop without ending a
+/// sentence, in lowercase and without their final full stop
+const SENTENCE_ABBREVIATIONS: &[&str] = &[
+    "mr", "mrs", "ms", "dr", "prof", "sr", "jr", "st", "vs", "etc", "e.g", "i.e", "cf", "no", "fig",
+];
+
+/// Count the number of sentences in a string. This is a heuristic: a sentence
+/// ends at a word, split as for `count_words`, that ends in `.`, `!` or `?`
+/// once any closing quotes and brackets are removed. A word ending in `.` does
+/// not end a sentence if it is a common abbreviation, such as `Mr.` or `e.g.`,
+/// or a single letter initial, such as `J.`, unless it is the last word of
+/// the input. Runs of punctuation such as `?!` or `...` end one sentence.
+///
+/// ```
+/// assert_eq!(ccwc::count_sentences("Mr. Smith went to Washington."), 1);
+/// assert_eq!(ccwc::count_sentences("Hello! How are you? Fine."), 3);
+/// ```
+pub fn count_sentences(input_string: &str) -> usize {
+    let mut words = input_string.split_whitespace().peekable();
+    let mut sentences = 0;
+
+    while let Some(word) = words.next() {
+        let word = word.trim_end_matches(['"', '\'', ')', ']', '\u{201d}', '\u{2019}']);
+        let is_last = words.peek().is_none();
+
+        if word.ends_with(['!', '?']) {
+            sentences += 1;
+        } else if let Some(stem) = word.strip_suffix('.') {
+            if is_last || !is_abbreviation(stem) {
+                sentences += 1;
+            }
+        }
+    }
+
+    sentences
+}
+
+/// Returns true if a word, without its final full stop, looks like an
+/// abbreviation or an initial rather than the end of a sentence
+fn is_abbreviation(stem: &str) -> bool {
+    let stem = stem.trim_start_matches(['"', '\'', '(', '[', '\u{201c}', '\u{2018}']);
+    let mut chars = stem.chars();
+    let is_initial = matches!((chars.next(), chars.next()), (Some(c), None) if c.is_alphabetic());
+
+    is_initial || SENTENCE_ABBREVIATIONS.contains(&stem.to_lowercase().as_str())
+}
+
+/// The size of the chunks read by `count_streaming`
+const STREAMING_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Counts the given reader chunk by chunk rather than reading it all into
+/// memory first. The counts specified in the config are calculated and the
+/// filename is taken from the config. Produces the same results as counting
+/// the whole input at once. Counts that need the whole input, such as the
+/// number of unique words, are made after reading all of it into memory.
+/// Returns an error with kind `InvalidData` if the input is not valid UTF-8.
+/// Only the first bytes of the reader up to the limit in the config, if there
+/// is one, are counted.
+pub fn count_streaming<R: Read>(reader: R, config: &CountConfig) -> Result<CountResult, io::Error> {
+    count_streaming_in_chunks(reader, config, STREAMING_CHUNK_SIZE)
+}
+
+/// The state that has to be carried from one chunk to the next when counting
+/// a stream
+#[derive(Debug, Default)]
+struct StreamingCounts {
+    line_separator: char,
+    bytes: usize,
+    chars: usize,
+    lines: usize,
+    words: usize,
+    in_word: bool,
+    word_length: usize,
+    max_word_length: usize,
+    line_length: usize,
+    previous_was_cr: bool,
+    line_lengths: LineLengths,
+    tabs: usize,
+    spaces: usize,
+}
+
+impl StreamingCounts {
+    /// Adds the counts for a piece of text which must directly follow the
+    /// text previously added
+    fn add(&mut self, text: &str) {
+        self.bytes += text.len();
+
+        for c in text.chars() {
+            self.chars += 1;
+
+            if c == self.line_separator {
+                self.lines += 1;
+            }
+
+            match c {
+                '\t' => self.tabs += 1,
+                ' ' => self.spaces += 1,
+                _ => {}
+            }
+
+            if c.is_whitespace() {
+                self.in_word = false;
+                self.word_length = 0;
+            } else {
+                if !self.in_word {
+                    self.in_word = true;
+                    self.words += 1;
+                }
+                self.word_length += 1;
+                self.max_word_length = self.max_word_length.max(self.word_length);
+            }
+
+            if c == '\n' {
+                // As with `str::lines` a `\r` directly before the `\n` is
+                // part of the line terminator
+                let line_length = self.line_length - usize::from(self.previous_was_cr);
+                self.line_lengths.add(line_length);
+                self.line_length = 0;
+            } else {
+                self.line_length += c.len_utf8();
+            }
+            self.previous_was_cr = c == '\r';
+        }
+    }
+
+    /// The tally of the line lengths, including the final line if it does
+    /// not end in a newline
+    fn line_lengths(&self) -> LineLengths {
+        let mut line_lengths = self.line_lengths;
+        if self.line_length > 0 {
+            line_lengths.add(self.line_length);
+        }
+        line_lengths
+    }
+}
+
+/// Implementation of `count_streaming` that reads chunks of the given size
+fn count_streaming_in_chunks<R: Read>(
+    mut reader: R,
+    config: &CountConfig,
+    chunk_size: usize,
+) -> Result<CountResult, io::Error> {
+    let to_io_error = |e: CcwcError| match e {
+        CcwcError::IoError(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+    };
+
+    if config.needs_whole_input() || config.limit.is_some() {
+        let input = config.read_input(reader).map_err(to_io_error)?;
+        let counter = Counter::new(config.clone()).count_raw(input).map_err(to_io_error)?;
+        return Ok(counter.result());
+    }
+
+    let mut counts = StreamingCounts {
+        line_separator: char::from(config.line_separator),
+        ..Default::default()
+    };
+    let mut chunk = vec![0; chunk_size];
+    // Bytes at the end of the previous chunk that could not be counted yet,
+    // either because they are the start of a multi-byte character or because
+    // they are a `\r` that may be the start of a `\r\n` in CRLF mode
+    let mut pending: Vec<u8> = Vec::new();
+
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        pending.extend_from_slice(&chunk[..read]);
+
+        let valid_up_to = match std::str::from_utf8(&pending) {
+            Ok(text) => text.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        let mut complete = valid_up_to;
+        if config.crlf_mode && complete > 0 && pending[complete - 1] == b'\r' {
+            complete -= 1;
+        }
+
+        let text = std::str::from_utf8(&pending[..complete]).expect("checked to be valid UTF-8");
+        if config.crlf_mode {
+            counts.add(&normalize_crlf(text));
+        } else {
+            counts.add(text);
+        }
+        pending.drain(..complete);
+    }
+
+    let text = std::str::from_utf8(&pending)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    counts.add(text);
+
+    let mut counter = Counter::new(config.clone());
+    counter.byte_or_char_count = match config.count_chars {
+        CharCount::Chars => Some(counts.chars),
+        CharCount::GraphemeClusters => unreachable!("grapheme clusters need the whole input"),
+        CharCount::Bytes => Some(counts.bytes),
+        CharCount::None => None,
+    };
+    counter.line_count = config.count_lines.then_some(counts.lines);
+    counter.word_count = (config.count_words == WordMode::Whitespace).then_some(counts.words);
+    let line_lengths = counts.line_lengths();
+    counter.max_line_length = config.count_max_line_length.then_some(line_lengths.max_bytes);
+    counter.max_word_length = config.count_max_word_length.then_some(counts.max_word_length);
+    counter.line_lengths = config.count_line_stats.then_some(line_lengths);
+    counter.tab_count = config.count_tabs.then_some(counts.tabs);
+    counter.space_count = config.count_spaces.then_some(counts.spaces);
+
+    Ok(counter.result())
+}
+
+/// A running tally of the lengths in bytes of lines, from which the LineStats
+/// are calculated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct LineLengths {
+    lines: usize,
+    total_bytes: usize,
+    min_bytes: usize,
+    max_bytes: usize,
+}
+
+impl LineLengths {
+    /// Adds a line of the given length in bytes to the tally
+    fn add(&mut self, length: usize) {
+        if self.lines == 0 || length < self.min_bytes {
+            self.min_bytes = length;
+        }
+        self.max_bytes = self.max_bytes.max(length);
+        self.total_bytes += length;
+        self.lines += 1;
+    }
+
+    /// Combines the tallies of two inputs into the tally of all of their lines
+    fn merge(self, other: LineLengths) -> LineLengths {
+        if self.lines == 0 {
+            return other;
+        }
+        if other.lines == 0 {
+            return self;
+        }
+
+        LineLengths {
+            lines: self.lines + other.lines,
+            total_bytes: self.total_bytes + other.total_bytes,
+            min_bytes: self.min_bytes.min(other.min_bytes),
+            max_bytes: self.max_bytes.max(other.max_bytes),
+        }
+    }
+
+    /// Calculates the LineStats from the tally
+    fn stats(&self) -> LineStats {
+        LineStats {
+            lines: self.lines,
+            min_line_bytes: self.min_bytes,
+            max_line_bytes: self.max_bytes,
+            mean_line_bytes: if self.lines == 0 {
+                0.0
+            } else {
+                self.total_bytes as f64 / self.lines as f64
+            },
+        }
+    }
+}
+
+/// A running total of a number of lengths, from which their Average is
+/// calculated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct LengthTotal {
+    count: usize,
+    total: usize,
+}
+
+impl LengthTotal {
+    /// Adds a length to the total
+    fn add(&mut self, length: usize) {
+        self.count += 1;
+        self.total += length;
+    }
+
+    /// Combines the totals of two inputs into the total of all of their lengths
+    fn merge(self, other: LengthTotal) -> LengthTotal {
+        LengthTotal {
+            count: self.count + other.count,
+            total: self.total + other.total,
+        }
+    }
+
+    /// Calculates the Average of the lengths
+    fn average(&self) -> Average {
+        Average {
+            count: self.count,
+            mean: if self.count == 0 {
+                0.0
+            } else {
+                self.total as f64 / self.count as f64
+            },
+        }
+    }
+}
+
+/// Totals the lengths in characters of the words in a string, where words
+/// are split in the same way as for `count_words`
+fn word_lengths(input_string: &str) -> LengthTotal {
+    let mut lengths = LengthTotal::default();
+    for word in input_string.split_whitespace() {
+        lengths.add(word.chars().count());
+    }
+    lengths
+}
+
+/// The mean length in characters of the words in a string, where words are
+/// split in the same way as for `count_words`. The mean is zero if there are
+/// no words.
+///
+/// ```
+/// assert_eq!(ccwc::average_word_length("a bb cccccc"), 3.0);
+/// assert_eq!(ccwc::average_word_length(""), 0.0);
+/// ```
+pub fn average_word_length(input_string: &str) -> f64 {
+    word_lengths(input_string).average().mean
+}
+
+/// Totals the lengths in characters of the lines in a string, where lines are
+/// split in the same way as for `max_line_length`
+fn line_char_lengths(input_string: &str) -> LengthTotal {
+    let mut lengths = LengthTotal::default();
+    for line in input_string.lines() {
+        lengths.add(line.chars().count());
+    }
+    lengths
+}
+
+/// The mean length in characters of the lines in a string, not including
+/// their terminators, where lines are split in the same way as for
+/// `max_line_length`. The mean is zero if there are no lines.
+///
+/// ```
+/// assert_eq!(ccwc::average_line_length("a\nbcd\n"), 2.0);
+/// assert_eq!(ccwc::average_line_length(""), 0.0);
+/// ```
+pub fn average_line_length(input_string: &str) -> f64 {
+    line_char_lengths(input_string).average().mean
+}
+
+/// Makes a Histogram of the lengths of the lines in a string in characters,
+/// with the given number of ranges of 10 lengths each. Lines longer than the
+/// last range are counted in it, so only `buckets` counts are ever kept
+/// however long the lines are. Lines are split in the same way as for
+/// `max_line_length`.
+///
+/// ```
+/// use std::num::NonZeroUsize;
+///
+/// let buckets = NonZeroUsize::new(2).unwrap();
+/// let histogram = ccwc::line_length_histogram("a\nbcd\n\nefghijklmnopqrstuvwxyz\n", buckets);
+/// assert_eq!(histogram.counts, vec![3, 1]);
+/// ```
+pub fn line_length_histogram(input_string: &str, buckets: NonZeroUsize) -> Histogram {
+    line_length_histogram_separated_by(input_string, buckets, b'\n')
+}
+
+/// Makes a Histogram of the lengths of the lines in a string in the same way
+/// as `line_length_histogram`, where the lines end with the given separator
+/// byte instead of a newline, such as NUL for `--null-data`
+fn line_length_histogram_separated_by(
+    input_string: &str,
+    buckets: NonZeroUsize,
+    separator: u8,
+) -> Histogram {
+    let last = buckets.get() - 1;
+    let mut counts = vec![0; buckets.get()];
+    let lines: Box<dyn Iterator<Item = &str>> = if separator == b'\n' {
+        Box::new(input_string.lines())
+    } else {
+        Box::new(input_string.split_terminator(char::from(separator)))
+    };
+    for line in lines {
+        counts[(line.chars().count() / HISTOGRAM_BUCKET_SIZE).min(last)] += 1;
+    }
+    Histogram { bucket_size: HISTOGRAM_BUCKET_SIZE, counts }
+}
+
+/// Tallies the lengths in bytes of the lines in a string. Lines are split in
+/// the same way as for `max_line_length`.
+fn line_lengths(input_string: &str) -> LineLengths {
+    let mut lengths = LineLengths::default();
+    for line in input_string.lines() {
+        lengths.add(line.len());
+    }
+    lengths
+}
+
+/// Combines two optional values with the given function. If only one of the
+/// values is present then that value is returned.
+fn combine<T>(a: Option<T>, b: Option<T>, f: impl FnOnce(T, T) -> T) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(f(a, b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// Adds two optional counts together. If only one of the counts is present
+/// then that count is returned.
+fn add_counts(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    combine(a, b, |a, b| a + b)
+}
+
+/// The header row of the CSV output
+const CSV_HEADER: &str = "filename,lines,words,bytes,chars,max_line_length,max_word_length,\
+    min_line_bytes,max_line_bytes,mean_line_bytes,unique_words,unique_lines,duplicate_lines,\
+    paragraphs,non_blank_lines,tabs,spaces,control_chars,non_ascii,crlf,matching_lines,\
+    sentences,average_word_length,average_line_length";
+
+/// Quotes a CSV field if it contains a comma, double quote, or line break.
+/// Double quotes within the field are escaped by doubling them.
+fn quote_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Formats the results as comma separated values with a header row naming
+/// the columns. Counts that were not requested are left empty as is the
+/// filename when reading from stdin.
+pub fn format_csv(results: &[CountResult]) -> String {
+    let mut rows = vec![CSV_HEADER.to_owned()];
+    rows.extend(results.iter().map(csv_row));
+    rows.join("\n")
+}
+
+/// Formats a single result as a row of comma separated values in the order of
+/// `CSV_HEADER`
+fn csv_row(result: &CountResult) -> String {
+    let mut fields = vec![quote_csv_field(result.filename.as_deref().unwrap_or(""))];
+    for count in [
+        result.lines,
+        result.words,
+        result.bytes,
+        result.chars,
+        result.max_line_length,
+        result.max_word_length,
+    ] {
+        fields.push(count.map(|count| count.to_string()).unwrap_or_default());
+    }
+    match &result.line_stats {
+        Some(line_stats) => {
+            fields.push(line_stats.min_line_bytes.to_string());
+            fields.push(line_stats.max_line_bytes.to_string());
+            fields.push(format!("{:.2}", line_stats.mean_line_bytes));
+        }
+        None => fields.extend([String::new(), String::new(), String::new()]),
+    }
+    for count in [
+        result.unique_words,
+        result.unique_lines,
+        result.duplicate_lines,
+        result.paragraphs,
+        result.non_blank_lines,
+        result.tabs,
+        result.spaces,
+        result.control_chars,
+        result.non_ascii,
+        result.crlf,
+        result.matching_lines,
+        result.sentences,
+    ] {
+        fields.push(count.map(|count| count.to_string()).unwrap_or_default());
+    }
+    for average in [result.average_word_length, result.average_line_length] {
+        fields.push(average.map(|average| average.to_string()).unwrap_or_default());
+    }
+    fields.join(",")
+}
+
+/// Appends a row with the timestamp and the result to the CSV file at the
+/// given path, starting it with a header row if it does not exist or is
+/// empty. The new contents are written to a temporary file next to it which
+/// then replaces it, so that the file is never left half written.
+pub fn append_to_csv(
+    path: &str,
+    result: &CountResult,
+    timestamp: DateTime<Utc>,
+) -> Result<(), CcwcError> {
+    let mut contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    if contents.is_empty() {
+        contents.push_str(&format!("timestamp,{CSV_HEADER}\n"));
+    } else if !contents.ends_with('\n') {
+        // Start on a new line if the last row was not terminated
+        contents.push('\n');
+    }
+    let timestamp = timestamp.to_rfc3339_opts(SecondsFormat::Secs, true);
+    contents.push_str(&format!("{timestamp},{}\n", csv_row(result)));
+
+    let temp_path = format!("{path}.{}.tmp", std::process::id());
+    fs::write(&temp_path, contents)?;
+    if let Err(e) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+/// Splits a NUL separated list of filenames, as used by `--files0-from`, into
+/// the individual filenames. A trailing NUL does not add an empty filename.
+fn split_files0(contents: &str) -> Vec<String> {
+    contents
+        .split_terminator('\0')
+        .map(str::to_owned)
+        .collect()
+}
+
+/// The errors that can occur while counting
+#[derive(Debug)]
+pub enum CcwcError {
+    /// Reading an input failed
+    IoError(io::Error),
+    /// An input was not valid UTF-8
+    InvalidUtf8(FromUtf8Error),
+    /// An input was not valid in the encoding given by `--encoding`
+    InvalidEncoding(Encoding),
+    /// An input was not valid JSON with `--input-format json`
+    #[cfg(feature = "json")]
+    InvalidJson(serde_json::Error),
+    /// The named input file does not exist
+    FileNotFound(String),
+    /// Two Counters could not be merged because they do not make the same
+    /// counts
+    IncompatibleConfigs,
+    /// The named input file looks like it is binary and binary files are
+    /// being skipped
+    BinaryFile(String),
+    /// The combination of command line arguments cannot be used together
+    InvalidArguments(String),
+    /// A count was outside of the bounds given by `--assert-min` or
+    /// `--assert-max`
+    CountOutOfBounds(String),
+    /// The file given to `--watch` could not be watched
+    #[cfg(feature = "watch")]
+    WatchError(notify::Error),
+    /// The results could not be converted to JSON
+    #[cfg(feature = "json")]
+    JsonError(serde_json::Error),
+}
+
+impl fmt::Display for CcwcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CcwcError::IoError(e) => write!(f, "{e}"),
+            CcwcError::InvalidUtf8(e) => write!(f, "input is not valid UTF-8: {e}"),
+            CcwcError::InvalidEncoding(encoding) => write!(f, "input is not valid {encoding}"),
+            #[cfg(feature = "json")]
+            CcwcError::InvalidJson(e) => write!(f, "input is not valid JSON: {e}"),
+            CcwcError::FileNotFound(filename) => write!(f, "{filename}: No such file or directory"),
+            CcwcError::BinaryFile(filename) => write!(f, "{filename}: skipping binary file"),
+            CcwcError::IncompatibleConfigs => {
+                write!(f, "cannot combine counts that were made with different options")
+            }
+            CcwcError::InvalidArguments(message) => write!(f, "{message}"),
+            CcwcError::CountOutOfBounds(message) => write!(f, "{message}"),
+            #[cfg(feature = "watch")]
+            CcwcError::WatchError(e) => write!(f, "unable to watch file: {e}"),
+            #[cfg(feature = "json")]
+            CcwcError::JsonError(e) => write!(f, "unable to format JSON: {e}"),
+        }
+    }
+}
+
+impl Error for CcwcError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CcwcError::IoError(e) => Some(e),
+            CcwcError::InvalidUtf8(e) => Some(e),
+            #[cfg(feature = "watch")]
+            CcwcError::WatchError(e) => Some(e),
+            #[cfg(feature = "json")]
+            CcwcError::InvalidJson(e) => Some(e),
+            #[cfg(feature = "json")]
+            CcwcError::JsonError(e) => Some(e),
+            CcwcError::InvalidEncoding(_)
+            | CcwcError::FileNotFound(_)
+            | CcwcError::BinaryFile(_)
+            | CcwcError::IncompatibleConfigs
+            | CcwcError::InvalidArguments(_)
+            | CcwcError::CountOutOfBounds(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for CcwcError {
+    fn from(e: io::Error) -> CcwcError {
+        CcwcError::IoError(e)
+    }
+}
+
+impl From<FromUtf8Error> for CcwcError {
+    fn from(e: FromUtf8Error) -> CcwcError {
+        CcwcError::InvalidUtf8(e)
+    }
+}
+
+#[cfg(feature = "watch")]
+impl From<notify::Error> for CcwcError {
+    fn from(e: notify::Error) -> CcwcError {
+        CcwcError::WatchError(e)
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for CcwcError {
+    fn from(e: serde_json::Error) -> CcwcError {
+        CcwcError::JsonError(e)
+    }
+}
+
+/// Reads all of stdin
+fn read_stdin() -> Result<Vec<u8>, CcwcError> {
+    let mut contents = Vec::new();
+    io::stdin().read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+/// Reads all of the named file
+fn read_file(filename: &str) -> Result<Vec<u8>, CcwcError> {
+    let mut contents = Vec::new();
+    open_file(filename, None)?.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+/// Reads the NUL separated list of filenames from the given file, or from
+/// stdin if the path is `-`.
+fn read_files0_from(path: &str) -> Result<Vec<String>, CcwcError> {
+    let contents = if path == "-" {
+        read_stdin()?
+    } else {
+        read_file(path)?
+    };
+
+    Ok(split_files0(&String::from_utf8(contents)?))
+}
+
+/// Opens the named file for reading. Files whose names end in `.gz` are
+/// decompressed as they are read so that the counts are of their contents. If
+/// a progress threshold is given and the file is at least that many bytes then
+/// the progress of reading it is printed to stderr.
+fn open_file<'a>(
+    filename: &'a str,
+    progress_threshold: Option<u64>,
+) -> Result<Box<dyn Read + 'a>, CcwcError> {
+    let file = fs::File::open(filename).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => CcwcError::FileNotFound(filename.to_owned()),
+        _ => CcwcError::IoError(e),
+    })?;
+
+    let size = file.metadata()?.len();
+    let reader: Box<dyn Read> = match progress_threshold {
+        Some(threshold) if size >= threshold => Box::new(ProgressReader::new(file, filename, size)),
+        _ => Box::new(file),
+    };
+
+    if filename.ends_with(".gz") {
+        Ok(Box::new(GzDecoder::new(reader)))
+    } else {
+        Ok(reader)
+    }
+}
+
+/// Wraps a reader to print to stderr how much of it has been read, as
+/// `ccwc: processing filename (X MiB / Y MiB)`, overwriting the previous
+/// progress each time and at most 10 times a second. The progress is cleared
+/// once the whole of the reader has been read.
+struct ProgressReader<'a, R> {
+    inner: R,
+    filename: &'a str,
+    size: u64,
+    read: u64,
+    last_printed: Option<Instant>,
+}
+
+impl<'a, R: Read> ProgressReader<'a, R> {
+    /// Create a ProgressReader for a reader of the named file of the given
+    /// size in bytes
+    fn new(inner: R, filename: &'a str, size: u64) -> ProgressReader<'a, R> {
+        ProgressReader { inner, filename, size, read: 0, last_printed: None }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.read += read as u64;
+
+        if read == 0 && !buf.is_empty() {
+            if self.last_printed.is_some() {
+                eprint!("\r\x1b[K");
+            }
+        } else if self.last_printed.is_none_or(|last| last.elapsed() >= Duration::from_millis(100)) {
+            let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+            eprint!(
+                "\r\x1b[Kccwc: processing {} ({:.1} MiB / {:.1} MiB)",
+                self.filename,
+                mib(self.read),
+                mib(self.size)
+            );
+            self.last_printed = Some(Instant::now());
+        }
+
+        Ok(read)
+    }
+}
+
+/// Reads the newline separated list of filenames from stdin, skipping any
+/// empty lines
+fn read_stdin_filenames() -> Result<Vec<String>, CcwcError> {
+    let contents = String::from_utf8(read_stdin()?)?;
+    Ok(contents.lines().filter(|line| !line.is_empty()).map(str::to_owned).collect())
+}
+
+/// Reads the named file and calculates the counts specified in the config
+fn count_file(config: &CountConfig, filename: String) -> Result<Counter, CcwcError> {
+    if let Some(counter) = count_file_from_metadata(config, &filename) {
+        return Ok(counter);
+    }
+
+    #[cfg(feature = "mmap")]
+    if let Some(counter) = count_file_mmap(config, &filename)? {
+        return Ok(counter);
+    }
+
+    let mut reader = open_file(&filename, config.progress_threshold())?;
+    let input = if config.skip_binary {
+        // Only the start of the file is needed to decide whether it is
+        // binary, so the rest is not read if it is
+        let mut start = Vec::new();
+        reader.by_ref().take(BINARY_CHECK_SIZE as u64).read_to_end(&mut start)?;
+        if is_likely_binary(&start) {
+            return Err(CcwcError::BinaryFile(filename.clone()));
+        }
+        config.read_input(Read::chain(start.as_slice(), reader))?
+    } else {
+        config.read_input(reader)?
+    };
+
+    let mut count_config = config.clone();
+    count_config.filename = Some(filename);
+    Counter::new(count_config).count_raw(input)
+}
+
+/// The number of bytes in the file at the path, taken from its metadata
+/// without reading it. Like `wc -c` this includes any byte order mark, so for
+/// UTF-8 files it is not the same as the number of characters counted by
+/// `-m`.
+///
+/// ```
+/// let bytes = ccwc::count_bytes_from_metadata(std::path::Path::new("test.txt"))?;
+/// assert_eq!(bytes, 342190);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn count_bytes_from_metadata(path: &Path) -> Result<u64, io::Error> {
+    Ok(fs::metadata(path)?.len())
+}
+
+/// Counts the bytes in the named file from its metadata when the config only
+/// counts bytes, so that the file does not have to be read. Returns `None`
+/// if the file should be read instead, because the config counts anything
+/// else or has to look at the contents, the file is compressed, or it is not
+/// a regular file with a size, such as a pipe or a file in `/proc` that
+/// reports a size of 0.
+fn count_file_from_metadata(config: &CountConfig, filename: &str) -> Option<Counter> {
+    if !config.has_same_counts(&CountConfig::for_bytes())
+        || config.limit.is_some()
+        || config.skip_binary
+        || filename.ends_with(".gz")
+    {
+        return None;
+    }
+    let path = Path::new(filename);
+    if !path.is_file() {
+        return None;
+    }
+    let bytes = count_bytes_from_metadata(path).ok().filter(|&bytes| bytes > 0)?;
+
+    let mut count_config = config.clone();
+    count_config.filename = Some(filename.to_owned());
+    let mut counter = Counter::new(count_config).count("");
+    counter.byte_or_char_count = Some(usize::try_from(bytes).ok()?);
+    Some(counter)
+}
+
+/// Counts the named file by memory mapping it, so that its contents are
+/// counted in place without being copied into a `String`. Returns `None`
+/// without counting if the file should be read normally instead, because it
+/// is smaller than the threshold in the config, is compressed, is not UTF-8,
+/// is not plain text, only has to be read up to a limit, or cannot be opened,
+/// in which case reading it reports the error.
+#[cfg(feature = "mmap")]
+fn count_file_mmap(config: &CountConfig, filename: &str) -> Result<Option<Counter>, CcwcError> {
+    if config.encoding != Encoding::Utf8
+        || config.detect_encoding
+        || config.input_format != InputFormat::Text
+        || filename.ends_with(".gz")
+        || config.limit.is_some()
+    {
+        return Ok(None);
+    }
+    let Ok(file) = fs::File::open(filename) else {
+        return Ok(None);
+    };
+    if file.metadata()?.len() < config.mmap_threshold {
+        return Ok(None);
+    }
+
+    // SAFETY: the map is only read while it is alive, and it is only valid
+    // for as long as no other process truncates or writes to the file. That
+    // cannot be prevented, so as with any program that maps a file the
+    // counts are only reliable if the file is not modified while it is being
+    // counted. The map is dropped before this function returns.
+    let mmap = unsafe { memmap2::Mmap::map(&file)? };
+    if config.skip_binary && is_likely_binary(&mmap) {
+        return Err(CcwcError::BinaryFile(filename.to_owned()));
+    }
+    let contents = if config.strip_bom { strip_bom(&mmap) } else { &mmap };
+    let Ok(contents) = std::str::from_utf8(contents) else {
+        return Ok(None);
+    };
+
+    let mut count_config = config.clone();
+    count_config.filename = Some(filename.to_owned());
+    Ok(Some(Counter::new(count_config).count(contents)))
+}
+
+/// Extracts the string values from a JSON document, at any depth, each
+/// followed by a newline. Array elements keep their order, but the values of
+/// an object come out in the order of their keys rather than as written. Keys
+/// and other values such as numbers are left out.
+///
+/// ```
+/// let text = ccwc::extract_text_from_json(r#"{"title": "Hello", "tags": ["a b", 1]}"#)?;
+/// assert_eq!(text, "a b\nHello\n");
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[cfg(feature = "json")]
+pub fn extract_text_from_json(input: &str) -> Result<String, serde_json::Error> {
+    fn push_strings(value: &serde_json::Value, text: &mut String) {
+        match value {
+            serde_json::Value::String(string) => {
+                text.push_str(string);
+                text.push('\n');
+            }
+            serde_json::Value::Array(values) => values.iter().for_each(|value| push_strings(value, text)),
+            serde_json::Value::Object(map) => map.values().for_each(|value| push_strings(value, text)),
+            _ => {}
+        }
+    }
+
+    let mut text = String::new();
+    push_strings(&serde_json::from_str(input)?, &mut text);
+    Ok(text)
+}
+
+/// Extracts the values of the fields from comma separated values, with the
+/// fields of each record separated by a space and the records by newlines.
+/// Quoted fields may contain commas, newlines, and quotes escaped as `""`.
+///
+/// ```
+/// let text = ccwc::extract_text_from_csv("name,quote\r\nAda,\"Hello, \"\"world\"\"\"\r\n");
+/// assert_eq!(text, "name quote\nAda Hello, \"world\"\n");
+/// ```
+pub fn extract_text_from_csv(input: &str) -> String {
+    let mut text = String::with_capacity(input.len());
+    let mut quoted = false;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                text.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => text.push(' '),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+/// Guesses the encoding of the input from its byte order mark if it has one,
+/// or from the bytes in it otherwise
+fn detect_encoding(input: &[u8]) -> &'static encoding_rs::Encoding {
+    if let Some((encoding, _)) = encoding_rs::Encoding::for_bom(input) {
+        return encoding;
+    }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(input, true);
+    detector.guess(None, true)
+}
+
+/// Decodes the input from the encoding that it looks to be in if `detect` is
+/// set, or else from the given encoding. Returns the decoded string and the
+/// encoding that was detected, if any.
+fn decode_input(
+    input: Vec<u8>,
+    encoding: Encoding,
+    detect: bool,
+) -> Result<(String, Option<&'static encoding_rs::Encoding>), CcwcError> {
+    if !detect {
+        return Ok((encoding.decode(input)?, None));
+    }
+
+    let detected = detect_encoding(&input);
+    let contents = if detected == encoding_rs::UTF_8 {
+        String::from_utf8(input)?
+    } else {
+        detected.decode_without_bom_handling(&input).0.into_owned()
+    };
+    Ok((contents, Some(detected)))
+}
+
+/// Returns the input without the UTF-8 byte order mark, `EF BB BF`, at its
+/// start, or all of the input if it does not start with one
+fn strip_bom(input: &[u8]) -> &[u8] {
+    input.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(input)
+}
+
+/// The number of bytes at the start of an input that are looked at to guess
+/// whether it is binary
+const BINARY_CHECK_SIZE: usize = 8 * 1024;
+
+/// Guesses whether the input is binary rather than text, in the same way as
+/// tools like `grep` and `diff`, by looking for a NUL byte in its first 8KiB
+fn is_likely_binary(input: &[u8]) -> bool {
+    input.iter().take(BINARY_CHECK_SIZE).any(|&b| b == 0)
+}
+
+/// Counts each of the named files with the given config, returning their
+/// results in the same order as the paths. Files that are skipped because
+/// they are binary are left out of the results.
+fn process_file_list(
+    paths: impl Iterator<Item = String>,
+    config: &CountConfig,
+) -> Result<Vec<CountResult>, CcwcError> {
+    Ok(count_file_list(paths, config)?.iter().map(Counter::result).collect())
+}
+
+/// Counts each of the named files with the given config in the same way as
+/// `process_file_list`, but returns the Counters rather than their results
+fn count_file_list(
+    paths: impl Iterator<Item = String>,
+    config: &CountConfig,
+) -> Result<Vec<Counter>, CcwcError> {
+    // Each file is counted independently so with the parallel feature they
+    // are spread across threads. The results keep the order of the paths
+    // either way.
+    let paths: Vec<String> = paths.collect();
+    #[cfg(feature = "parallel")]
+    let files = paths.into_par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let files = paths.into_iter();
+
+    let results = files
+        .map(|filename| match count_file(config, filename) {
+            Ok(counter) => Ok(Some(counter)),
+            Err(e @ CcwcError::BinaryFile(_)) => {
+                eprintln!("ccwc: {e}");
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        })
+        .collect::<Result<Vec<Option<Counter>>, CcwcError>>()?;
+
+    Ok(results.into_iter().flatten().collect())
+}
+
+/// The names of the files given on the command line, by `--files0-from` or
+/// `--from-nul-stdin`, or by `--from-stdin-filenames`, with any directories
+/// replaced by the files in them if `-r` was given. Returns `None` if stdin
+/// should be counted instead, which is when no files are named in any of
+/// these ways.
+fn input_filenames(cli: &Cli) -> Result<Option<Vec<String>>, CcwcError> {
+    let files0_from = if cli.from_nul_stdin {
+        Some("-")
+    } else {
+        cli.files0_from.as_deref()
+    };
+
+    let filenames = match files0_from {
+        Some(_) if !cli.filenames.is_empty() => {
+            return Err(CcwcError::InvalidArguments(
+                "file operands cannot be combined with --files0-from".to_owned(),
+            ));
+        }
+        Some(files0_from) => read_files0_from(files0_from)?,
+        None if cli.from_stdin_filenames => read_stdin_filenames()?,
+        None if cli.filenames.is_empty() => return Ok(None),
+        None => cli.filenames.clone(),
+    };
+
+    if cli.recursive {
+        expand_directories(cli, filenames).map(Some)
+    } else {
+        Ok(Some(filenames))
+    }
+}
+
+/// Replaces each directory in the filenames with the regular files in it and
+/// its subdirectories, in order of their paths, that pass the `--include`
+/// and `--exclude` filters. Stops with a warning once there are as many files
+/// as `--max-files`.
+fn expand_directories(cli: &Cli, filenames: Vec<String>) -> Result<Vec<String>, CcwcError> {
+    let max_files = cli.max_files.unwrap_or(usize::MAX);
+    let include = parse_globs(&cli.include)?;
+    let exclude = parse_globs(&cli.exclude)?;
+    let is_selected = |entry: &walkdir::DirEntry| {
+        let name = entry.file_name().to_string_lossy();
+        (include.is_empty() || include.iter().any(|glob| glob.matches(&name)))
+            && !exclude.iter().any(|glob| glob.matches(&name))
+    };
+
+    let mut expanded = Vec::new();
+    for filename in filenames {
+        if expanded.len() == max_files {
+            eprintln!("ccwc: warning: stopped after {max_files} files, see --max-files");
+            break;
+        }
+        if !Path::new(&filename).is_dir() {
+            expanded.push(filename);
+            continue;
+        }
+
+        let walker = WalkDir::new(&filename)
+            .follow_links(!cli.no_follow_symlinks)
+            .sort_by_file_name();
+        for entry in walker {
+            let entry = entry.map_err(io::Error::from)?;
+            if !entry.file_type().is_file() || !is_selected(&entry) {
+                continue;
+            }
+            if expanded.len() == max_files {
+                eprintln!("ccwc: warning: stopped after {max_files} files, see --max-files");
+                return Ok(expanded);
+            }
+            expanded.push(entry.path().to_string_lossy().into_owned());
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// Parses the `--include` or `--exclude` glob patterns
+fn parse_globs(globs: &[String]) -> Result<Vec<Pattern>, CcwcError> {
+    globs
+        .iter()
+        .map(|glob| {
+            Pattern::new(glob).map_err(|e| {
+                CcwcError::InvalidArguments(format!("invalid glob `{glob}`: {e}"))
+            })
+        })
+        .collect()
+}
+
+/// Counts how many times each word occurs across all of the inputs given in
+/// the Cli struct. The inputs are read and prepared in the same way as for
+/// the other counts, so that for example `--limit`, `--strip-bom`,
+/// `--comment-strip` and `--ignore-case` apply to the words too.
+fn run_frequency(cli: &Cli) -> Result<HashMap<String, usize>, CcwcError> {
+    let config = CountConfig::from_cli(cli);
+    let counters = match input_filenames(cli)? {
+        None => vec![count_reader(&config, io::stdin())?],
+        Some(filenames) => count_file_list(filenames.into_iter(), &config)?,
+    };
+
+    Ok(counters
+        .into_iter()
+        .filter_map(|counter| counter.word_frequency)
+        .reduce(merge_frequencies)
+        .unwrap_or_default())
+}
+
+/// Runs the counts specified in the Cli struct on everything read from the
+/// reader, such as an `io::Cursor` in a test, in the same way as when reading
+/// stdin. Any files named in the Cli struct are ignored.
+///
+/// ```
+/// use ccwc::prelude::*;
+///
+/// let cli = Cli::parse_from(["ccwc", "-lw"]);
+/// let result = ccwc::run_with_reader(cli, std::io::Cursor::new(b"one two\nthree\n"))?;
+/// assert_eq!((result.lines, result.words), (Some(2), Some(3)));
+/// # Ok::<(), CcwcError>(())
+/// ```
+pub fn run_with_reader<R: Read>(cli: Cli, reader: R) -> Result<CountResult, CcwcError> {
+    Ok(count_reader(&CountConfig::from_cli(&cli), reader)?.result())
+}
+
+/// Reads all of the reader, up to any limit in the config, and calculates the
+/// counts specified in the config
+fn count_reader<R: Read>(config: &CountConfig, reader: R) -> Result<Counter, CcwcError> {
+    let input = config.read_input(reader)?;
+    Counter::new(config.clone()).count_raw(input)
+}
+
+/// Runs the counts specified in the Cli struct in the same way as
+/// `run_structured`, and returns the same results, but reads and counts the
+/// files on Tokio's blocking thread pool and reads stdin with Tokio, so that
+/// the runtime's threads are not blocked while waiting on them.
+///
+/// ```
+/// use ccwc::prelude::*;
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+/// let results = runtime.block_on(ccwc::run_async(Cli::parse_from(["ccwc", "-l", "test.txt"])))?;
+/// assert_eq!(results[0].lines, Some(7145));
+/// # Ok::<(), CcwcError>(())
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn run_async(cli: Cli) -> Result<Vec<CountResult>, CcwcError> {
+    let config = CountConfig::from_cli(&cli);
+    let Some(filenames) = input_filenames(&cli)? else {
+        let mut input = Vec::new();
+        tokio::io::stdin().read_to_end(&mut input).await?;
+        let counter = count_blocking(move || count_reader(&config, input.as_slice())).await?;
+        return finish_results(&cli, 1, vec![counter.result()]);
+    };
+
+    let inputs = filenames.len();
+    let mut results = Vec::with_capacity(inputs);
+    for filename in filenames {
+        let config = config.clone();
+        match count_blocking(move || count_file(&config, filename)).await {
+            Ok(counter) => results.push(counter.result()),
+            Err(e @ CcwcError::BinaryFile(_)) => eprintln!("ccwc: {e}"),
+            Err(e) => return Err(e),
+        }
+    }
+
+    finish_results(&cli, inputs, results)
+}
+
+/// Runs the counting for `run_async` on Tokio's blocking thread pool
+#[cfg(feature = "tokio")]
+async fn count_blocking<F>(count: F) -> Result<Counter, CcwcError>
+where
+    F: FnOnce() -> Result<Counter, CcwcError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(count)
+        .await
+        .map_err(|e| CcwcError::IoError(io::Error::other(e)))?
+}
+
+/// The public interface to the library. Takes in a Cli struct and runs the
+/// counts specified therein reading from each file or stdin as required.
+/// Returns one CountResult per input and, if more than one file is given or
+/// the `--total` option says to, a final CountResult with the totals whose
+/// filename is `total`.
+pub fn run_structured(cli: Cli) -> Result<Vec<CountResult>, CcwcError> {
+    let (inputs, results) = count_inputs(&cli)?;
+    finish_results(&cli, inputs, results)
+}
+
+/// Counts each of the inputs given in the Cli struct, returning the number of
+/// inputs and their results. The number of inputs rather than of results,
+/// which leave out any skipped binary files, decides whether there is a
+/// total by default.
+fn count_inputs(cli: &Cli) -> Result<(usize, Vec<CountResult>), CcwcError> {
+    let count_config = CountConfig::from_cli(cli);
+    Ok(match input_filenames(cli)? {
+        None => (1, vec![count_reader(&count_config, io::stdin())?.result()]),
+        Some(filenames) => {
+            (filenames.len(), process_file_list(filenames.into_iter(), &count_config)?)
+        }
+    })
+}
+
+/// Checks the results of counting the given number of inputs against the
+/// `--assert-*` bounds, sorts them, filters them by the `--min-*` and
+/// `--max-*` limits, and adds the total if there should be one, as set by
+/// the Cli struct
+fn finish_results(
+    cli: &Cli,
+    inputs: usize,
+    mut results: Vec<CountResult>,
+) -> Result<Vec<CountResult>, CcwcError> {
+    // The bounds are on each input rather than on the total
+    cli.check_bounds(&results)?;
+
+    if let Some(field) = cli.sort_by {
+        field.sort(&mut results);
+        if cli.sort_reverse {
+            results.reverse();
+        }
+    }
+
+    let print_total = match cli.total {
+        TotalMode::Auto => inputs > 1,
+        TotalMode::Always | TotalMode::Only => true,
+        TotalMode::Never => false,
+    };
+    let total = print_total.then(|| {
+        let mut total: CountResult = results.iter().cloned().sum();
+        total.filename = (!cli.omits_filenames()).then(|| "total".to_owned());
+        total
+    });
+
+    results.retain(|result| cli.in_limits(result));
+    if let Some(total) = total {
+        if cli.total == TotalMode::Only {
+            results.clear();
+        }
+        results.push(total);
+    }
+
+    Ok(results)
+}
+
+/// Whether the counts of a file named in a `--check` file still match
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckOutcome {
+    pub filename: String,
+    pub matches: bool,
+    /// Why the file could not be counted, such as it not existing, in which
+    /// case its counts do not match
+    pub error: Option<String>,
+}
+
+/// Formats the outcome as `<file>: OK`, `<file>: FAILED`, or, if the file
+/// could not be counted, `<file>: FAILED open or read` as `md5sum --check`
+/// does
+impl fmt::Display for CheckOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match (self.matches, &self.error) {
+            (_, Some(_)) => "FAILED open or read",
+            (true, None) => "OK",
+            (false, None) => "FAILED",
+        };
+        write!(f, "{}: {status}", self.filename)
+    }
+}
+
+/// Splits a line of ccwc's output into its first `columns` whitespace
+/// separated count columns and the filename after them. Returns `None` if
+/// the line has too few columns or no filename.
+///
+/// ```
+/// let line = "   2   5 my file.txt";
+/// assert_eq!(ccwc::parse_count_line(line, 2), Some((vec!["2", "5"], "my file.txt")));
+/// assert_eq!(ccwc::parse_count_line("   2", 1), None);
+/// ```
+pub fn parse_count_line(line: &str, columns: usize) -> Option<(Vec<&str>, &str)> {
+    let mut counts = Vec::with_capacity(columns);
+    let mut rest = line;
+
+    for _ in 0..columns {
+        rest = rest.trim_start();
+        let end = rest.find(char::is_whitespace)?;
+        counts.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    let filename = rest.trim_start();
+    (!filename.is_empty()).then_some((counts, filename))
+}
+
+/// Reads the file of counts given to `--check` and counts each of the files
+/// named in it again with the counts specified in the Cli struct, which
+/// should be the same as those the file was made with. Returns whether the
+/// counts of each file still match, in the order of the file. A file that
+/// cannot be counted, for example because it no longer exists, does not match
+/// and the rest are still checked. Blank lines and the line with the total
+/// are skipped, and any other line that is not in ccwc's output format is an
+/// error.
+pub fn check(cli: &Cli) -> Result<Vec<CheckOutcome>, CcwcError> {
+    let path = cli.check.as_deref().ok_or_else(|| {
+        CcwcError::InvalidArguments("no file of counts was given to check".to_owned())
+    })?;
+    let config = CountConfig::from_cli(cli);
+    let format_options = FormatOptions::from_cli(cli);
+    let columns = Counter::new(config.clone()).count("").result().columns(&format_options).len();
+
+    let contents = String::from_utf8(read_file(path)?)?;
+    let mut outcomes = Vec::new();
+
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (expected, filename) = parse_count_line(line, columns).ok_or_else(|| {
+            CcwcError::InvalidArguments(format!(
+                "line {} of {path} is not in ccwc's output format",
+                number + 1
+            ))
+        })?;
+        if filename == "total" {
+            continue;
+        }
+
+        outcomes.push(match count_file(&config, filename.to_owned()) {
+            Ok(counter) => CheckOutcome {
+                filename: filename.to_owned(),
+                matches: counter.result().columns(&format_options) == expected,
+                error: None,
+            },
+            Err(e) => CheckOutcome {
+                filename: filename.to_owned(),
+                matches: false,
+                error: Some(e.to_string()),
+            },
+        });
+    }
+
+    Ok(outcomes)
+}
+
+/// Makes the single count specified in the Cli struct, such as `-l`, of
+/// every input together and returns whether it is more than zero, without
+/// printing anything. With `--line-stats` any of its columns being more than
+/// zero is enough. Returns an error unless exactly one count was specified.
+pub fn run_quiet(cli: Cli) -> Result<bool, CcwcError> {
+    if cli.count_flags_set() != 1 {
+        return Err(CcwcError::InvalidArguments(
+            "--quiet needs exactly one count, such as -l".to_owned(),
+        ));
+    }
+
+    let cli = Cli { total: TotalMode::Never, ..cli };
+    let total: CountResult = run_structured(cli)?.into_iter().sum();
+    Ok(total.named_counts().into_iter().any(|(_, count)| count.is_above(0)))
+}
+
+/// Prints the counts of the single file given in the Cli struct and then
+/// prints them again, overwriting the previous counts, every time the file
+/// changes. Runs until the file can no longer be watched. If the file is
+/// deleted then a message is printed to stderr and the counts are printed
+/// again once it is recreated.
+#[cfg(feature = "watch")]
+pub fn watch(cli: Cli) -> Result<(), CcwcError> {
+    let filename = match cli.filenames.as_slice() {
+        [] => {
+            return Err(CcwcError::InvalidArguments(
+                "--watch cannot be used when reading from stdin".to_owned(),
+            ));
+        }
+        [filename] => filename.clone(),
+        _ => {
+            return Err(CcwcError::InvalidArguments(
+                "--watch can only be used with a single file".to_owned(),
+            ));
+        }
+    };
+    let path = Path::new(&filename);
+    let output_format = OutputFormat::from_cli(&cli);
+    let count_config = CountConfig::from_cli(&cli);
+
+    // Plain counts are a single line that can be overwritten, but JSON and
+    // CSV are printed in full each time so that they can still be parsed
+    let print_counts = || -> Result<(), CcwcError> {
+        let result = count_file(&count_config, filename.clone())?.result();
+        let output = output_format.format(&[result])?;
+        if output_format.is_structured() {
+            println!("{output}");
+        } else {
+            print!("\r\x1b[K{output}");
+        }
+        Ok(io::stdout().flush()?)
+    };
+    print_counts()?;
+
+    // The directory is watched rather than the file itself so that the file
+    // can be deleted and recreated
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    watcher.watch(directory, RecursiveMode::NonRecursive)?;
+
+    for event in receiver {
+        let event = event?;
+        if !event.paths.iter().any(|changed| changed.file_name() == path.file_name()) {
+            continue;
+        }
+
+        match event.kind {
+            EventKind::Remove(_) if !path.exists() => {
+                eprintln!("\nccwc: {filename}: file removed, waiting for it to be recreated");
+            }
+            EventKind::Create(_) | EventKind::Modify(_) if path.exists() => print_counts()?,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the counts specified in the Cli struct and formats the results as
+/// they should be printed, one line per input or as JSON or CSV if requested.
+/// With `--freq` the word frequencies are printed instead, and with `--diff`
+/// the changes in the counts between the two files. With `--zero-terminated`
+/// every line, including the last, ends with a NUL character.
+pub fn run(cli: Cli) -> Result<String, CcwcError> {
+    if cli.frequency {
+        return Ok(format_frequency(run_frequency(&cli)?, cli.top));
+    }
+
+    if let Some(files) = &cli.diff {
+        let config = CountConfig::from_cli(&cli);
+        let before = count_file(&config, files[0].clone())?.result();
+        let after = count_file(&config, files[1].clone())?.result();
+        return Ok(diff_results(&before, &after).to_string());
+    }
+
+    let output_format = OutputFormat::from_cli(&cli);
+
+    // The histogram is of every input, even those left out of the output by
+    // the `--min-*` and `--max-*` limits
+    let (inputs, results) = count_inputs(&cli)?;
+    let histogram = results
+        .iter()
+        .filter_map(|result| result.line_length_histogram.clone())
+        .reduce(Histogram::merge);
+    let results = finish_results(&cli, inputs, results)?;
+    report_encodings(&results);
+
+    if let Some(path) = &cli.append {
+        let timestamp = Utc::now();
+        for result in &results {
+            append_to_csv(path, result, timestamp)?;
+        }
+    }
+
+    let output = output_format.format(&results)?;
+    if output_format.is_structured() || output_format.zero_terminated {
+        return Ok(output);
+    }
+    Ok(match histogram {
+        Some(histogram) if output.is_empty() => histogram.to_string(),
+        Some(histogram) => format!("{output}\n\n{histogram}"),
+        None => output,
+    })
+}
+
+/// Prints the encoding that was detected for each of the results with
+/// `--encoding-detect` to stderr
+fn report_encodings(results: &[CountResult]) {
+    for result in results {
+        if let Some(encoding) = &result.encoding {
+            let label = result.filename.as_ref().map(|f| format!("{f}: ")).unwrap_or_default();
+            eprintln!("ccwc: {label}detected encoding {encoding}");
+        }
+    }
+}
+
+/// How `run` and `watch` print the results, as set by the Cli struct
+struct OutputFormat {
+    #[cfg(feature = "json")]
+    json: bool,
+    csv: bool,
+    zero_terminated: bool,
+    options: FormatOptions,
+}
+
+impl OutputFormat {
+    fn from_cli(cli: &Cli) -> OutputFormat {
+        OutputFormat {
+            #[cfg(feature = "json")]
+            json: cli.json,
+            csv: cli.csv,
+            zero_terminated: cli.zero_terminated,
+            options: FormatOptions::from_cli(cli),
+        }
+    }
+
+    /// Returns true if the results are printed as JSON or CSV rather than in
+    /// columns
+    fn is_structured(&self) -> bool {
+        #[cfg(feature = "json")]
+        if self.json {
+            return true;
+        }
+        self.csv
+    }
+
+    /// Formats the results as CSV, as JSON, or one line per result with any
+    /// header, in which case each line ends with a NUL character rather than
+    /// being separated by newlines if `--zero-terminated` was given
+    fn format(&self, results: &[CountResult]) -> Result<String, CcwcError> {
+        if self.csv {
+            return Ok(format_csv(results));
+        }
+
+        #[cfg(feature = "json")]
+        if self.json {
+            return match results {
+                [result] => Ok(serde_json::to_string(result)?),
+                _ => Ok(serde_json::to_string(results)?),
+            };
+        }
+
+        let mut rows = format_all(results, &self.options);
+        if self.options.header {
+            if let Some(header) = format_header(results, &self.options) {
+                rows.insert(0, header);
+            }
+        }
+        if self.zero_terminated {
+            return Ok(rows.iter().map(|row| format!("{row}\0")).collect());
+        }
+        Ok(rows.join("\n"))
+    }
+}
+
+/// Opens the target that the output of `run_to` should be written to: the
+/// file given to `--output-file`, which is truncated, the file given to
+/// `--output-append`, or stdout.
+pub fn output_target(cli: &Cli) -> Result<Box<dyn Write>, CcwcError> {
+    if let Some(path) = &cli.output_file {
+        return Ok(Box::new(fs::File::create(path)?));
+    }
+    if let Some(path) = &cli.output_append {
+        return Ok(Box::new(fs::OpenOptions::new().create(true).append(true).open(path)?));
+    }
+    Ok(Box::new(io::stdout()))
+}
+
+/// Runs the counts specified in the Cli struct in the same way as `run` and
+/// writes the output, followed by a newline unless it is NUL terminated, to
+/// the given writer
+pub fn run_to(cli: Cli, output: &mut dyn Write) -> Result<(), CcwcError> {
+    let zero_terminated = cli.zero_terminated;
+    let result = run(cli)?;
+    if zero_terminated {
+        write!(output, "{result}")?;
+    } else {
+        writeln!(output, "{result}")?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Makes a fresh temporary directory for the named test, which is removed
+    /// along with everything written to it when it is dropped
+    fn temp_dir(test_name: &str) -> tempfile::TempDir {
+        tempfile::Builder::new().prefix(&format!("ccwc-{test_name}-")).tempdir().unwrap()
+    }
+
+    /// Writes the given contents to a file in the temporary directory and
+    /// returns the path of the file
+    fn write_temp_file(dir: &tempfile::TempDir, filename: &str, contents: &str) -> String {
+        let path = dir.path().join(filename);
+        fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn test_counter_byte_count() {
+        let config = CountConfig::from_cli(
+            &Cli {
+                byte_count: true,
+                ..Default::default()
+            }
+        );
+        let result = Counter::new(config).count("hello, world");
+
+        assert_eq!(
+            result.byte_or_char_count.unwrap(),
+            12
+        );
+        assert_eq!(
+            result.line_count,
+            None
+        );
+        assert_eq!(
+            result.word_count,
+            None
+        );
+    }
+
+    #[test]
+    fn test_counter_char_count() {
+        let config = CountConfig::from_cli(
+            &Cli {
+                char_count: true,
+                ..Default::default()
+            }
+        );
+        let result = Counter::new(config).count("hello, world");
+
+        assert_eq!(
+            result.byte_or_char_count.unwrap(),
+            12
+        );
+        assert_eq!(
+            result.line_count,
+            None
+        );
+        assert_eq!(
+            result.word_count,
+            None
+        );
+    }
+
+    #[test]
+    fn test_counter_line_count() {
+        let config = CountConfig::from_cli(
+            &Cli {
+                line_count: true,
+                ..Default::default()
+            }
+        );
+        let result = Counter::new(config).count("hello, world\n");
+
+        assert_eq!(
+            result.byte_or_char_count,
+            None
+        );
+        assert_eq!(
+            result.line_count.unwrap(),
+            1
+        );
+        assert_eq!(
+            result.word_count,
+            None
+        );
+    }
+
+    #[test]
+    fn test_counter_word_count() {
+        let config = CountConfig::from_cli(
+            &Cli {
+                word_count: true,
+                ..Default::default()
+            }
+        );
+        let result = Counter::new(config).count("hello, world");
+
+        assert_eq!(
+            result.byte_or_char_count,
+            None
+        );
+        assert_eq!(
+            result.line_count,
+            None
+        );
+        assert_eq!(
+            result.word_count.unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_count_raw() {
+        let config = CountConfig::builder().bytes(true).encoding(Encoding::Utf16Le).build();
+        let counter = Counter::new(config).count_raw(b"h\0\xe9\0\n\0".to_vec()).unwrap();
+        assert_eq!(counter.byte_or_char_count, Some(6));
+
+        let config = CountConfig::builder().bytes(true).encoding(Encoding::Latin1).build();
+        let counter = Counter::new(config).count_raw(b"caf\xe9\n".to_vec()).unwrap();
+        assert_eq!(counter.byte_or_char_count, Some(5));
+        assert_eq!(count_bytes_raw(b"caf\xe9\n"), 5);
+
+        let counter = Counter::new(CountConfig::for_bytes()).count_raw(b"\xff".to_vec());
+        assert!(matches!(counter, Err(CcwcError::InvalidUtf8(_))));
+    }
+
+    #[test]
+    fn test_locale() {
+        let input = "cafe\u{301} 👍🏽\n";
+        let chars = |locale: &str| {
+            let cli = Cli::parse_from(["ccwc", "-m", "--locale", locale]);
+            let config = CountConfig::from_cli(&cli);
+            let counted = Counter::new(config.clone()).count(input).result().chars;
+            assert_eq!(count_streaming(input.as_bytes(), &config).unwrap().chars, counted);
+            counted
+        };
+
+        assert_eq!(chars("C"), Some(16));
+        assert_eq!(chars("UTF-8"), Some(9));
+        assert_eq!(chars("utf-8"), Some(9));
+        assert_eq!(chars("unicode"), Some(7));
+        assert_eq!(CountConfig::from_cli(&Cli::parse_from(["ccwc", "-m"])).locale, Locale::Utf8);
+        assert!(Cli::try_parse_from(["ccwc", "--locale", "fr_FR"]).is_err());
+    }
+
+    #[test]
+    fn test_grapheme_count() {
+        assert_eq!(count_graphemes(""), 0);
+        assert_eq!(count_graphemes("abc"), 3);
+        assert_eq!(count_graphemes("e\u{301}"), 1);
+        assert_eq!(count_graphemes("👋🏿 🇬🇧\r\n"), 4);
+
+        let input = "cafe\u{301} 👍🏽\n";
+        let config = CountConfig::from_cli(&Cli::parse_from(["ccwc", "-g", "-m", "-c"]));
+        let result = Counter::new(config.clone()).count(input).result();
+        assert_eq!(result.chars, Some(7));
+        assert_eq!(result.bytes, None);
+        assert_eq!(count_streaming(input.as_bytes(), &config).unwrap(), result);
+        assert_eq!(config, CountConfig::builder().graphemes(true).chars(true).build());
+        assert_eq!(CountConfig::for_chars().union(config.clone()).count_chars, CharCount::GraphemeClusters);
+    }
+
+    #[test]
+    fn test_count_bytes() {
+        assert_eq!(count_bytes(""), 0);
+        assert_eq!(count_bytes("Hello, world!"), 13);
+        assert_eq!(count_bytes("こんにちは"), 15);
+    }
+
+    #[test]
+    fn test_count_characters() {
+        assert_eq!(count_characters(""), 0);
+        assert_eq!(count_characters("Hello, world!"), 13);
+        assert_eq!(count_characters("こんにちは"), 5);
+    }
+
+    #[test]
+    fn test_count_lines() {
+        assert_eq!(count_lines(""), 0);
+        assert_eq!(count_lines("Hello\nworld\n"), 2);
+        assert_eq!(count_lines("Line 1\nLine 2\nLine 3\n"), 3);
+    }
+
+    #[test]
+    fn test_count_lines_matches_gnu_wc() {
+        // Expected values are the output of GNU `wc -l` on the same input
+        assert_eq!(count_lines("hello"), 0);
+        assert_eq!(count_lines("hello\n"), 1);
+        assert_eq!(count_lines("hello\n\n"), 2);
+        assert_eq!(count_lines("\n"), 1);
+        assert_eq!(count_lines("Hello\nworld"), 1);
+        assert_eq!(count_lines("a\r\nb\r\n"), 2);
+    }
+
+    #[test]
+    fn test_count_logical_lines() {
+        // Unlike `count_lines`, which counts newlines as POSIX `wc -l` does,
+        // these agree with `str::lines().count()`, so a final line without a
+        // trailing newline is counted
+        for input in ["", "hello", "hello\n", "hello\n\n", "\n", "Hello\nworld", "a\r\nb\r\n"] {
+            assert_eq!(count_logical_lines(input), input.lines().count(), "{input:?}");
+        }
+
+        let config = CountConfig::builder().lines(true).logical_lines(true).build();
+        assert_eq!(Counter::new(config).count("one\ntwo").line_count, Some(2));
+    }
+
+    #[test]
+    fn test_count_lines_separated_by() {
+        assert_eq!(count_lines_separated_by("", b'\0'), 0);
+        assert_eq!(count_lines_separated_by("a\0b\0", b'\0'), 2);
+        assert_eq!(count_lines_separated_by("a\nb\0c", b'\0'), 1);
+        assert_eq!(count_lines_separated_by("a\nb\n", b'\n'), 2);
+        assert_eq!(count_logical_lines_separated_by("a\0b", b'\0'), 2);
+    }
+
+    #[test]
+    fn test_counter_null_data() {
+        let config = CountConfig::from_cli(
+            &Cli {
+                line_count: true,
+                null_data: true,
+                ..Default::default()
+            }
+        );
+        let result = Counter::new(config).count("./a b.txt\0./c\nd.txt\0");
+
+        assert_eq!(result.line_count.unwrap(), 2);
+    }
+
+    #[test]
+    fn test_count_words() {
+        assert_eq!(count_words(""), 0);
+        assert_eq!(count_words("Hello,\nworld!"), 2);
+        assert_eq!(count_words("This is a sentence."), 4);
+    }
+
+    #[test]
+    fn test_sum_count_results() {
+        let cli = Cli::default();
+        let results = vec![
+            Counter::new(CountConfig::from_cli(&cli)).count("hello, world\n").result(),
+            Counter::new(CountConfig::from_cli(&cli)).count("one\ntwo three\n").result(),
+        ];
+        let result: CountResult = results.into_iter().sum();
+
+        assert_eq!(result.lines.unwrap(), 3);
+        assert_eq!(result.words.unwrap(), 5);
+        assert_eq!(result.bytes.unwrap(), 27);
+        assert_eq!(result.filename, None);
+    }
+
+    #[test]
+    fn test_add_count_results_options() {
+        let a = CountResult {
+            lines: Some(1),
+            words: Some(2),
+            max_line_length: Some(5),
+            filename: Some("a.txt".to_owned()),
+            ..Default::default()
+        };
+        let b = CountResult {
+            lines: Some(3),
+            bytes: Some(4),
+            max_line_length: Some(2),
+            filename: Some("b.txt".to_owned()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            a + b,
+            CountResult {
+                // Some + Some
+                lines: Some(4),
+                // Some + None
+                words: Some(2),
+                // None + Some
+                bytes: Some(4),
+                // None + None
+                chars: None,
+                max_line_length: Some(5),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_sum_no_count_results() {
+        let result: CountResult = Vec::new().into_iter().sum();
+
+        assert_eq!(result, CountResult::default());
+    }
+
+    #[test]
+    fn test_counter_max_line_length() {
+        let config = CountConfig::from_cli(
+            &Cli {
+                max_line_length: true,
+                ..Default::default()
+            }
+        );
+        let result = Counter::new(config).count("hello\nworld!\n");
+
+        assert_eq!(result.byte_or_char_count, None);
+        assert_eq!(result.line_count, None);
+        assert_eq!(result.word_count, None);
+        assert_eq!(result.max_line_length.unwrap(), 6);
+    }
+
+    #[test]
+    fn test_counter_crlf_mode() {
+        let config = CountConfig::from_cli(
+            &Cli {
+                crlf: true,
+                ..Default::default()
+            }
+        );
+        let result = Counter::new(config).count("one\r\ntwo\nthree\r\n");
+
+        assert_eq!(result.byte_or_char_count.unwrap(), 14);
+        assert_eq!(result.line_count.unwrap(), 3);
+        assert_eq!(result.word_count.unwrap(), 3);
+    }
+
+    #[test]
+    fn test_normalize_crlf() {
+        assert_eq!(normalize_crlf(""), "");
+        assert_eq!(normalize_crlf("a\r\nb\r\n"), "a\nb\n");
+        assert_eq!(normalize_crlf("a\r\nb\nc\rd"), "a\nb\nc\rd");
+    }
+
+    #[test]
+    fn test_extract_text_from_csv() {
+        assert_eq!(extract_text_from_csv(""), "");
+        assert_eq!(extract_text_from_csv("a,b,c\n1,,3\n"), "a b c\n1  3\n");
+        assert_eq!(extract_text_from_csv("\"multi\nline\",x\r\n"), "multi\nline x\n");
+        assert_eq!(extract_text_from_csv("\"say \"\"hi\"\"\""), "say \"hi\"");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_extract_text_from_json() {
+        assert_eq!(extract_text_from_json("[]").unwrap(), "");
+        assert_eq!(extract_text_from_json(r#"[1, true, null, "one two"]"#).unwrap(), "one two\n");
+        assert_eq!(extract_text_from_json(r#"{"a": {"b": ["x", {"c": "y z"}]}}"#).unwrap(), "x\ny z\n");
+        assert!(extract_text_from_json("{").is_err());
+    }
+
+    #[test]
+    fn test_counter_input_format() {
+        let config = CountConfig::builder().lines(true).words(true).bytes(true);
+        let input = b"name,comment\nAda,\"one, two\"\n".to_vec();
+        let counter = Counter::new(config.input_format(InputFormat::Csv).build());
+        let result = counter.count_raw(input).unwrap().result();
+        assert_eq!((result.lines, result.words, result.bytes), (Some(2), Some(5), Some(26)));
+
+        #[cfg(feature = "json")]
+        {
+            let config = CountConfig::builder().lines(true).words(true).bytes(true);
+            let counter = Counter::new(config.input_format(InputFormat::Json).build());
+            let result = counter.count_raw(br#"{"text": "hello world", "n": 3}"#.to_vec()).unwrap().result();
+            assert_eq!((result.lines, result.words, result.bytes), (Some(1), Some(2), Some(12)));
+
+            let counter = Counter::new(CountConfig::builder().input_format(InputFormat::Json).build());
+            assert!(matches!(counter.count_raw(b"not json".to_vec()), Err(CcwcError::InvalidJson(_))));
+        }
+    }
+
+    #[test]
+    fn test_strip_comments() {
+        assert_eq!(strip_comments("", CommentStyle::Hash), "");
+        assert_eq!(
+            strip_comments("#!/bin/sh\necho '#1' \"#2\" # three\n\n  # indented\nexit\n", CommentStyle::Hash),
+            "echo '#1' \"#2\" \n\nexit\n"
+        );
+        assert_eq!(
+            strip_comments("SELECT '--' -- all\nFROM t;--\n", CommentStyle::DoubleDash),
+            "SELECT '--' \nFROM t;\n"
+        );
+        assert_eq!(
+            strip_comments("a // \"b\"\n\"c\\\"//\" // d", CommentStyle::DoubleSlash),
+            "a \n\"c\\\"//\" "
+        );
+        assert_eq!(
+            strip_comments("/* header\n * more\n */\nint/* x */main;\nf(\"/*\");\n", CommentStyle::Block),
+            "int main;\nf(\"/*\");\n"
+        );
+        // A C comment ends at the first `*/`, and one that is never closed
+        // runs to the end of the input
+        assert_eq!(strip_comments("a /* b /* c */ d */\ne /* f\ng\n", CommentStyle::Block), "a   d */\ne \n");
+    }
+
+    #[test]
+    fn test_counter_comment_style() {
+        let input = "# comment\nx = 1  # set x\n\ny = 2\n";
+        let config = CountConfig::builder().lines(true).words(true).bytes(true);
+        let stripped =
+            Counter::new(config.comment_style(Some(CommentStyle::Hash)).build()).count(input).result();
+        assert_eq!((stripped.lines, stripped.words, stripped.bytes), (Some(3), Some(6), Some(32)));
+
+        let cli = Cli::parse_from(["ccwc", "--comment-strip=--"]);
+        assert_eq!(CountConfig::from_cli(&cli).comment_style, Some(CommentStyle::DoubleDash));
+        let cli = Cli::parse_from(["ccwc", "--comment-strip", "/* */"]);
+        assert_eq!(CountConfig::from_cli(&cli).comment_style, Some(CommentStyle::Block));
+    }
+
+    #[test]
+    fn test_detect_encoding() {
+        assert_eq!(detect_encoding(b"plain ASCII\n"), encoding_rs::UTF_8);
+        assert_eq!(detect_encoding("こんにちは\n".as_bytes()), encoding_rs::UTF_8);
+        assert_eq!(detect_encoding(b"\xFF\xFEa\0"), encoding_rs::UTF_16LE);
+        assert_eq!(detect_encoding(b"caf\xE9 na\xEFve, d\xE9j\xE0 vu\n"), encoding_rs::WINDOWS_1252);
+    }
+
+    #[test]
+    fn test_counter_detect_encoding() {
+        let input = b"caf\xE9 na\xEFve, d\xE9j\xE0 vu\n".to_vec();
+        let config = CountConfig::builder().words(true).chars(true).detect_encoding(true);
+        let result = Counter::new(config.build()).count_raw(input.clone()).unwrap().result();
+        assert_eq!((result.words, result.chars), (Some(4), Some(20)));
+        assert_eq!(result.encoding.as_deref(), Some("windows-1252"));
+
+        let config = CountConfig::builder().bytes(true).detect_encoding(true);
+        let result = Counter::new(config.build()).count_raw(input.clone()).unwrap().result();
+        assert_eq!(result.bytes, Some(20));
+
+        // The bytes are still counted in the detected encoding when the line
+        // endings are normalized, and so are the characters of the C locale
+        let crlf_input = b"caf\xE9 na\xEFve, d\xE9j\xE0 vu\r\n".to_vec();
+        let config = CountConfig::builder().bytes(true).crlf(true).detect_encoding(true);
+        let result = Counter::new(config.build()).count_raw(crlf_input).unwrap().result();
+        assert_eq!(result.bytes, Some(20));
+        let config = CountConfig::builder().chars(true).locale(Locale::C).detect_encoding(true);
+        let result = Counter::new(config.build()).count_raw(input.clone()).unwrap().result();
+        assert_eq!(result.chars, Some(20));
+
+        // Without detection the input is not valid UTF-8
+        let counter = Counter::new(CountConfig::builder().chars(true).build());
+        assert!(matches!(counter.count_raw(input), Err(CcwcError::InvalidUtf8(_))));
+        assert_eq!(CountResult::from("abc").encoding, None);
+    }
+
+    #[test]
+    fn test_count_tokens() {
+        fn whitespace(s: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+            Box::new(s.split_whitespace())
+        }
+        assert_eq!(count_tokens("", whitespace), 0);
+        assert_eq!(count_tokens(" one  two\tthree\n", whitespace), count_words(" one  two\tthree\n"));
+        assert_eq!(count_tokens("a-b c-d", |s| Box::new(s.split(['-', ' ']))), 4);
+        assert_eq!(count_tokens("ab\ncd", |s| Box::new(s.lines())), 2);
+    }
+
+    #[test]
+    fn test_trim_lines() {
+        assert_eq!(trim_lines("", '\n'), "");
+        assert_eq!(trim_lines("  padded  \n\tx y\t\n", '\n'), "padded\nx y\n");
+        assert_eq!(trim_lines("   \n\n \t \n", '\n'), "\n\n\n");
+        assert_eq!(trim_lines(" a \0 b \n", '\0'), "a\0b");
+    }
+
+    #[test]
+    fn test_counter_trim_whitespace() {
+        let input = "  padded line   \n      \n\tlast\n";
+        let config = CountConfig::builder().lines(true).words(true).bytes(true).max_line_length(true);
+        let untrimmed = Counter::new(config.clone().build()).count(input).result();
+        let trimmed = Counter::new(config.trim_whitespace(true).build()).count(input).result();
+
+        assert_eq!((trimmed.lines, trimmed.words), (untrimmed.lines, untrimmed.words));
+        assert_eq!(trimmed.bytes, Some(30));
+        assert_eq!(trimmed.bytes, untrimmed.bytes);
+        assert_eq!((untrimmed.max_line_length, trimmed.max_line_length), (Some(16), Some(11)));
+
+        // Lines of only spaces are still lines, but have no characters but
+        // their newlines once trimmed
+        let config = CountConfig::builder().lines(true).chars(true);
+        let untrimmed = Counter::new(config.clone().build()).count("   \n \n  ").result();
+        let trimmed = Counter::new(config.trim_whitespace(true).build()).count("   \n \n  ").result();
+        assert_eq!((untrimmed.lines, untrimmed.chars), (Some(2), Some(8)));
+        assert_eq!((trimmed.lines, trimmed.chars), (Some(2), Some(2)));
+    }
+
+    #[test]
+    fn test_max_line_length() {
+        assert_eq!(max_line_length(""), 0);
+        assert_eq!(max_line_length("Hello\nworld!"), 6);
+        assert_eq!(max_line_length("a\r\nabc\r\n"), 3);
+    }
+
+    #[test]
+    fn test_counter_result() {
+        let mut config = CountConfig::from_cli(&Cli::default());
+        config.filename = Some("test.txt".to_owned());
+        let result = Counter::new(config).count("hello, world\n").result();
+
+        assert_eq!(
+            result,
+            CountResult {
+                lines: Some(1),
+                words: Some(2),
+                bytes: Some(13),
+                filename: Some("test.txt".to_owned()),
+                ..Default::default()
+            }
+        );
+        assert_eq!(result.to_string(), "   1   2  13 test.txt");
+    }
+
+    #[test]
+    fn test_counter_display() {
+        let config = CountConfig::from_cli(
+            &Cli {
+                word_count: true,
+                max_line_length: true,
+                ..Default::default()
+            }
+        );
+        let counter = Counter::new(config).count("hello, world\n");
+
+        assert_eq!(format!("{counter}"), "   2  12");
+        assert_eq!(counter.to_string(), counter.result().to_string());
+    }
+
+    #[test]
+    fn test_split_files0() {
+        assert_eq!(split_files0(""), Vec::<String>::new());
+        assert_eq!(split_files0("a.txt\0b c.txt\0"), vec!["a.txt", "b c.txt"]);
+        assert_eq!(split_files0("a.txt\0with\nnewline"), vec!["a.txt", "with\nnewline"]);
+    }
+
+    #[test]
+    fn test_files0_from_with_filenames_is_an_error() {
+        let cli = Cli {
+            files0_from: Some("-".to_owned()),
+            filenames: vec!["test.txt".to_owned()],
+            ..Default::default()
+        };
+
+        assert!(matches!(run_structured(cli), Err(CcwcError::InvalidArguments(_))));
+    }
+
+    #[test]
+    fn test_format_csv_single_file() {
+        let results = vec![CountResult {
+            lines: Some(1),
+            words: Some(2),
+            bytes: Some(13),
+            filename: Some("test.txt".to_owned()),
+            ..Default::default()
+        }];
+
+        assert_eq!(
+            format_csv(&results),
+            format!("{CSV_HEADER}\ntest.txt,1,2,13,,,,,,,,,,,,,,,,,,,,")
+        );
+    }
+
+    #[test]
+    fn test_format_csv_multiple_files() {
+        let results = vec![
+            CountResult {
+                chars: Some(4),
+                max_line_length: Some(3),
+                filename: Some("a,b.txt".to_owned()),
+                ..Default::default()
+            },
+            CountResult {
+                chars: Some(6),
+                max_line_length: Some(5),
+                filename: Some("say \"hi\".txt".to_owned()),
+                ..Default::default()
+            },
+            CountResult {
+                chars: Some(10),
+                max_line_length: Some(5),
+                filename: Some("total".to_owned()),
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(
+            format_csv(&results),
+            format!(
+                "{CSV_HEADER}\n\
+                 \"a,b.txt\",,,,4,3,,,,,,,,,,,,,,,,,,\n\
+                 \"say \"\"hi\"\".txt\",,,,6,5,,,,,,,,,,,,,,,,,,\n\
+                 total,,,,10,5,,,,,,,,,,,,,,,,,,"
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_csv_stdin() {
+        let results = vec![CountResult {
+            lines: Some(0),
+            words: Some(0),
+            bytes: Some(0),
+            ..Default::default()
+        }];
+
+        assert_eq!(
+            format_csv(&results),
+            format!("{CSV_HEADER}\n,0,0,0,,,,,,,,,,,,,,,,,,,,")
+        );
+    }
+
+    #[test]
+    fn test_count_streaming_matches_counter() {
+        let inputs = [
+            "",
+            "hello, world",
+            "hello, world\n",
+            "one\r\ntwo\nthree\r\n\r",
+            "  leading and trailing  \n\n",
+            "こんにちは 世界\nmulti-byte\u{2003}words\n",
+            "first\0second\nline\0",
+            "no-break\u{a0}space\u{3000}ideographic\u{85}next\n",
+        ];
+        let clis = [
+            Cli::default(),
+            Cli { char_count: true, max_line_length: true, ..Default::default() },
+            Cli { crlf: true, max_line_length: true, ..Default::default() },
+            Cli { line_stats: true, ..Default::default() },
+            Cli { crlf: true, line_stats: true, ..Default::default() },
+            Cli { unique_words: true, ignore_case: true, ..Default::default() },
+            Cli { unique_lines: true, ..Default::default() },
+            Cli { max_word_length: true, crlf: true, ..Default::default() },
+            Cli { crlf: true, char_count: true, line_count: true, ..Default::default() },
+            Cli { null_data: true, ..Default::default() },
+        ];
+
+        for cli in &clis {
+            let config = CountConfig::from_cli(cli);
+            for input in inputs {
+                let expected = Counter::new(config.clone()).count(input).result();
+                for chunk_size in [1, 2, 3, 7, STREAMING_CHUNK_SIZE] {
+                    let result = count_streaming_in_chunks(input.as_bytes(), &config, chunk_size).unwrap();
+                    assert_eq!(result, expected, "input {input:?} chunk size {chunk_size}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_count_streaming_invalid_utf8() {
+        let config = CountConfig::from_cli(&Cli::default());
+
+        let result = count_streaming(&b"hello \xff world"[..], &config);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let result = count_streaming(&b"truncated \xe3\x81"[..], &config);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_count_file_gzip() {
+        use std::io::Write;
+        use flate2::{write::GzEncoder, Compression};
+
+        let temp = temp_dir("gzip");
+        let path = temp.path().join("test.txt.gz");
+        let mut encoder = GzEncoder::new(fs::File::create(&path).unwrap(), Compression::default());
+        encoder.write_all("hello, world\nこんにちは\n".as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let filename = path.to_str().unwrap().to_owned();
+        let config = CountConfig::from_cli(&Cli::default());
+        let result = count_file(&config, filename.clone()).unwrap().result();
+
+        assert_eq!(result.lines, Some(2));
+        assert_eq!(result.words, Some(3));
+        assert_eq!(result.bytes, Some(29));
+        assert_eq!(result.filename, Some(filename));
+    }
+
+    #[test]
+    fn test_count_config_builder() {
+        let config = CountConfig::builder()
+            .lines(true)
+            .bytes(true)
+            .chars(true)
+            .filename(Some("test.txt".to_owned()))
+            .build();
+        let result = Counter::new(config).count("こんにちは\n").result();
+
+        assert_eq!(
+            result,
+            CountResult {
+                lines: Some(1),
+                chars: Some(6),
+                filename: Some("test.txt".to_owned()),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_count_config_default_matches_no_flags() {
+        let from_cli = CountConfig::from_cli(&Cli::default());
+
+        assert_eq!(CountConfig::default(), from_cli);
+    }
+
+    #[test]
+    fn test_count_config_single_count_constructors() {
+        let input = "hello, world\nこんにちは\n";
+        let count = |config: CountConfig| Counter::new(config).count(input).result();
+
+        assert_eq!(count(CountConfig::for_bytes()), CountResult { bytes: Some(29), ..Default::default() });
+        assert_eq!(count(CountConfig::for_words()), CountResult { words: Some(3), ..Default::default() });
+        assert_eq!(count(CountConfig::for_lines()), CountResult { lines: Some(2), ..Default::default() });
+        assert_eq!(count(CountConfig::for_chars()), CountResult { chars: Some(19), ..Default::default() });
+    }
+
+    #[test]
+    fn test_config_union() {
+        let bytes = CountConfig::builder().bytes(true).filename(Some("a.txt".to_owned())).build();
+        let chars = CountConfig::builder()
+            .chars(true)
+            .crlf(true)
+            .filename(Some("b.txt".to_owned()))
+            .build();
+        let union = bytes.clone().union(chars.clone());
+
+        assert_eq!(union.count_chars, CharCount::Chars);
+        assert!(union.crlf_mode);
+        assert_eq!(union.filename.as_deref(), Some("a.txt"));
+        assert_eq!(chars.union(bytes.clone()).filename.as_deref(), Some("b.txt"));
+
+        let none = CountConfig::builder().build();
+        assert_eq!(none.clone().union(bytes.clone()).count_chars, CharCount::Bytes);
+        assert_eq!(none.clone().union(none.clone()), none);
+        assert_eq!(bytes.clone().union(bytes.clone()), bytes);
+    }
+
+    #[test]
+    fn test_counter_merge() {
+        let config = CountConfig::builder()
+            .lines(true)
+            .chars(true)
+            .max_line_length(true)
+            .line_stats(true)
+            .build();
+        let first = Counter::new(config.clone()).count("one\nthree\n");
+        let second = Counter::new(config.clone()).count("こんにちは\n");
+        let both = Counter::new(config).count("one\nthree\nこんにちは\n");
+
+        let merged = first.merge(second).unwrap().result();
+        assert_eq!(merged, both.result());
+        assert_eq!(merged.line_stats.unwrap().min_line_bytes, 3);
+    }
+
+    #[test]
+    fn test_counter_merge_incompatible() {
+        let words = Counter::new(CountConfig::for_words()).count("one");
+        let lines = Counter::new(CountConfig::for_lines()).count("one");
+
+        assert!(matches!(words.merge(lines), Err(CcwcError::IncompatibleConfigs)));
+    }
+
+    #[test]
+    fn test_count_config_and_counter_clone_eq() {
+        let config = CountConfig::builder().words(true).filename(Some("a.txt".to_owned())).build();
+        assert_eq!(config.clone(), config);
+        assert_ne!(config, CountConfig::for_words());
+        assert_eq!(config.count_chars, CharCount::None);
+
+        let counter = Counter::new(config).count("one two\n");
+        assert_eq!(counter.clone(), counter);
+        assert_ne!(counter.clone().merge(counter.clone()).unwrap(), counter);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_count_config_serde_round_trip() {
+        let config = CountConfig::builder().chars(true).line_stats(true).crlf(true).build();
+        let json = serde_json::to_string(&config).unwrap();
+
+        assert_eq!(serde_json::from_str::<CountConfig>(&json).unwrap(), config);
+    }
+
+    #[test]
+    fn test_count_config_builder_matches_cli() {
+        let from_builder = CountConfig::builder()
+            .words(true)
+            .bytes(true)
+            .null_data(true)
+            .build();
+        let from_cli = CountConfig::from_cli(
+            &Cli {
+                word_count: true,
+                byte_count: true,
+                null_data: true,
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(from_builder, from_cli);
+    }
+
+    #[test]
+    fn test_counter_line_stats() {
+        let config = CountConfig::from_cli(
+            &Cli {
+                line_stats: true,
+                ..Default::default()
+            }
+        );
+        let result = Counter::new(config).count("a\nabc\r\nabcde\n").result();
+
+        assert_eq!(
+            result,
+            CountResult {
+                line_stats: Some(LineStats {
+                    lines: 3,
+                    min_line_bytes: 1,
+                    max_line_bytes: 5,
+                    mean_line_bytes: 3.0,
+                }),
+                ..Default::default()
+            }
+        );
+        assert_eq!(result.to_string(), "       1       5    3.00");
+    }
+
+    #[test]
+    fn test_line_lengths_equal_lines() {
+        let stats = line_lengths("abcd\nefgh\nijkl\n").stats();
+
+        assert_eq!(stats.min_line_bytes, 4);
+        assert_eq!(stats.max_line_bytes, 4);
+        assert_eq!(stats.mean_line_bytes, 4.0);
+    }
+
+    #[test]
+    fn test_line_lengths_variable_lines() {
+        let stats = line_lengths("\na\n\nabcdefghijklmnopqrstuvwxyz\nこんにちは").stats();
+
+        assert_eq!(stats.min_line_bytes, 0);
+        assert_eq!(stats.max_line_bytes, 26);
+        assert_eq!(stats.mean_line_bytes, 42.0 / 5.0);
+    }
+
+    #[test]
+    fn test_line_lengths_empty() {
+        assert_eq!(line_lengths("").stats(), LineStats::default());
+    }
+
+    #[test]
+    fn test_sum_line_stats() {
+        let cli = Cli { line_stats: true, ..Default::default() };
+        let results = vec![
+            Counter::new(CountConfig::from_cli(&cli)).count("ab\nabcd\n").result(),
+            Counter::new(CountConfig::from_cli(&cli)).count("").result(),
+            Counter::new(CountConfig::from_cli(&cli)).count("abcdefghi\n").result(),
+        ];
+        let stats = results.into_iter().sum::<CountResult>().line_stats.unwrap();
+
+        assert_eq!(stats.lines, 3);
+        assert_eq!(stats.min_line_bytes, 2);
+        assert_eq!(stats.max_line_bytes, 9);
+        assert_eq!(stats.mean_line_bytes, 5.0);
+    }
+
+    #[test]
+    fn test_format_all_uniform_width() {
+        let results = vec![
+            CountResult {
+                lines: Some(1),
+                words: Some(2),
+                filename: Some("a.txt".to_owned()),
+                ..Default::default()
+            },
+            CountResult {
+                lines: Some(12345),
+                words: Some(6),
+                filename: Some("b.txt".to_owned()),
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(
+            format_all(&results, &FormatOptions::default()),
+            vec![
+                "       1       2 a.txt",
+                "   12345       6 b.txt",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_include_zero() {
+        let result = CountResult {
+            lines: Some(0),
+            chars: Some(5),
+            filename: Some("a.txt".to_owned()),
+            ..Default::default()
+        };
+        let options = FormatOptions { include_zero: true, ..Default::default() };
+
+        assert_eq!(
+            format_all(std::slice::from_ref(&result), &options),
+            vec!["   0   0   0   5 a.txt"]
+        );
+        assert_eq!(
+            format_all(&[result], &FormatOptions::default()),
+            vec!["   0   5 a.txt"]
+        );
+    }
+
+    #[test]
+    fn test_count_words_unicode_whitespace() {
+        assert_eq!(count_words("no-break\u{a0}space"), 2);
+        assert_eq!(count_words("em\u{2003}space"), 2);
+        assert_eq!(count_words("ideographic\u{3000}space"), 2);
+        assert_eq!(count_words("\u{2028}line\u{2029}separators\u{85}"), 2);
+        // Zero width space is not whitespace
+        assert_eq!(count_words("zero\u{200b}width"), 1);
+
+        let input = "a\u{a0}b\u{2003}c";
+        assert_eq!(words(input).count(), count_words(input));
+        let config = CountConfig::from_cli(&Cli { word_count: true, max_word_length: true, ..Default::default() });
+        let result = count_streaming(input.as_bytes(), &config).unwrap();
+        assert_eq!(result.words, Some(3));
+        assert_eq!(result.max_word_length, Some(1));
+    }
+
+    #[test]
+    fn test_words_matches_count_words() {
+        let input = "  こんにちは world\n\tthird\u{3000}word ";
+        let spans: Vec<_> = words(input).collect();
+
+        assert_eq!(spans.len(), count_words(input));
+        for (start, end, word) in spans {
+            assert_eq!(&input[start..end], word);
+        }
+        assert_eq!(words("").next(), None);
+        assert_eq!(words(" \n ").next(), None);
+    }
+
+    #[test]
+    fn test_lines_with_terminators() {
+        let input = "one\n\ntwo\r\nthree";
+        let lines: Vec<_> = lines_with_terminators(input).collect();
+
+        assert_eq!(lines, vec![(1, "one\n"), (2, "\n"), (3, "two\r\n"), (4, "three")]);
+        assert_eq!(lines.iter().filter(|(_, line)| line.ends_with('\n')).count(), count_lines(input));
+        assert_eq!(lines.iter().map(|(_, line)| *line).collect::<String>(), input);
+        assert_eq!(lines_with_terminators("").next(), None);
+    }
+
+    #[test]
+    fn test_latin1_encoding() {
+        let temp = temp_dir("latin1");
+        let path = write_temp_file(&temp, "a.txt", "");
+        fs::write(&path, b"caf\xe9 cr\xe8me\n").unwrap();
+        let cli = Cli {
+            char_count: true,
+            encoding: Encoding::Latin1,
+            filenames: vec![path.clone()],
+            ..Default::default()
+        };
+        assert_eq!(run_structured(cli).unwrap()[0].chars, Some(11));
+
+        let cli = Cli { encoding: Encoding::Latin1, filenames: vec![path], ..Default::default() };
+        let result = &run_structured(cli).unwrap()[0];
+        assert_eq!(result.bytes, Some(11));
+        assert_eq!(result.words, Some(2));
+    }
+
+    #[test]
+    fn test_utf16_encoding() {
+        let temp = temp_dir("utf16");
+        let path = write_temp_file(&temp, "a.txt", "");
+        let input: Vec<u8> = "\u{feff}hé\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        fs::write(&path, &input).unwrap();
+        let cli = Cli {
+            byte_count: true,
+            char_count: true,
+            encoding: Encoding::Utf16Le,
+            filenames: vec![path.clone()],
+            ..Default::default()
+        };
+        assert_eq!(run_structured(cli).unwrap()[0].chars, Some(4));
+
+        let cli = Cli {
+            byte_count: true,
+            encoding: Encoding::Utf16Le,
+            filenames: vec![path.clone()],
+            ..Default::default()
+        };
+        assert_eq!(run_structured(cli).unwrap()[0].bytes, Some(8));
+
+        fs::write(&path, &input[..7]).unwrap();
+        let cli = Cli { encoding: Encoding::Utf16Le, filenames: vec![path], ..Default::default() };
+        assert!(matches!(run_structured(cli), Err(CcwcError::InvalidEncoding(Encoding::Utf16Le))));
+    }
+
+    #[test]
+    fn test_column_separator() {
+        let results = vec![
+            CountResult {
+                lines: Some(1),
+                words: Some(12345),
+                filename: Some("a.txt".to_owned()),
+                ..Default::default()
+            },
+            CountResult { lines: Some(2), words: Some(6), ..Default::default() },
+        ];
+        let options = FormatOptions { column_separator: ",".to_owned(), ..Default::default() };
+
+        assert_eq!(format_all(&results, &options), vec!["1,12345,a.txt", "2,6"]);
+    }
+
+    #[test]
+    fn test_format_all_empty() {
+        assert_eq!(format_all(&[], &FormatOptions::default()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_stdin_label() {
+        let label = |args: &[&str]| {
+            let cli = Cli::parse_from([&["ccwc"], args].concat());
+            let config = CountConfig::from_cli(&cli);
+            Counter::new(config).count("one two\n").result().filename
+        };
+
+        assert_eq!(label(&[]), None);
+        assert_eq!(label(&["--stdin-label", "<stdin>"]), Some("<stdin>".to_owned()));
+        assert_eq!(label(&["--posix"]), Some("-".to_owned()));
+        assert_eq!(label(&["--posix", "--stdin-label", "pipe"]), Some("pipe".to_owned()));
+        assert_eq!(label(&["--posix", "--stdin-label", ""]), None);
+        assert_eq!(label(&["--posix", "--no-filename"]), None);
+    }
+
+    #[test]
+    fn test_no_filename() {
+        let temp = temp_dir("no-filename");
+        let a = write_temp_file(&temp, "a.txt", "hello, world\n");
+        let cli = Cli {
+            no_filename: true,
+            total: TotalMode::Always,
+            filenames: vec![a],
+            ..Default::default()
+        };
+        let results = run_structured(cli).unwrap();
+
+        assert_eq!(results[0].filename, None);
+        assert_eq!(results[0].to_string(), "   1   2  13");
+        assert_eq!(results[1].filename, None);
+    }
+
+    #[test]
+    fn test_total_modes() {
+        let temp = temp_dir("total-modes");
+        let a = write_temp_file(&temp, "a.txt", "one\n");
+        let b = write_temp_file(&temp, "b.txt", "two three\n");
+        let (a, b) = (a.as_str(), b.as_str());
+        let filenames = |mode, filenames: &[&str]| {
+            let cli = Cli {
+                line_count: true,
+                total: mode,
+                filenames: filenames.iter().map(|f| f.to_string()).collect(),
+                ..Default::default()
+            };
+            run_structured(cli)
+                .unwrap()
+                .into_iter()
+                .map(|result| result.filename.unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(filenames(TotalMode::Auto, &[a, b]), vec![a, b, "total"]);
+        assert_eq!(filenames(TotalMode::Auto, &[a]), vec![a]);
+        assert_eq!(filenames(TotalMode::Always, &[a]), vec![a, "total"]);
+        assert_eq!(filenames(TotalMode::Only, &[a, b]), vec!["total"]);
+        assert_eq!(filenames(TotalMode::Never, &[a, b]), vec![a, b]);
+    }
+
+    #[test]
+    fn test_output_format() {
+        let results = [CountResult { lines: Some(1), words: Some(2), ..Default::default() }];
+
+        let output_format = OutputFormat::from_cli(&Cli::default());
+        assert!(!output_format.is_structured());
+        assert_eq!(output_format.format(&results).unwrap(), "   1   2");
+
+        let output_format = OutputFormat::from_cli(&Cli { csv: true, ..Default::default() });
+        assert!(output_format.is_structured());
+        assert_eq!(output_format.format(&results).unwrap(), format_csv(&results));
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn test_watch_stdin_is_an_error() {
+        let cli = Cli { watch: true, ..Default::default() };
+
+        let error = watch(cli).unwrap_err();
+        assert_eq!(error.to_string(), "--watch cannot be used when reading from stdin");
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn test_watch_multiple_files_is_an_error() {
+        let cli = Cli {
+            watch: true,
+            filenames: vec!["a.txt".to_owned(), "b.txt".to_owned()],
+            ..Default::default()
+        };
+
+        let error = watch(cli).unwrap_err();
+        assert_eq!(error.to_string(), "--watch can only be used with a single file");
+    }
+
+    #[test]
+    fn test_count_unique_words() {
+        assert_eq!(count_unique_words(""), 0);
+        assert_eq!(count_unique_words("the cat and the hat"), 4);
+        assert_eq!(count_unique_words("The the THE"), 3);
+    }
+
+    #[test]
+    fn test_counter_unique_words() {
+        let config = CountConfig::from_cli(
+            &Cli {
+                unique_words: true,
+                ..Default::default()
+            }
+        );
+        let result = Counter::new(config).count("The cat and the hat\n");
+
+        assert_eq!(result.unique_word_count.unwrap(), 5);
+        assert_eq!(result.word_count, None);
+        assert_eq!(result.line_count, None);
+        assert_eq!(result.byte_or_char_count, None);
+    }
+
+    #[test]
+    fn test_counter_unique_words_ignore_case() {
+        let config = CountConfig::from_cli(
+            &Cli {
+                unique_words: true,
+                ignore_case: true,
+                ..Default::default()
+            }
+        );
+        let result = Counter::new(config).count("The cat and the hat\nTHE CAT\n");
+
+        assert_eq!(result.unique_word_count.unwrap(), 4);
+    }
+
+    #[test]
+    fn test_case_fold() {
+        assert_eq!(case_fold("Hello, World"), "hello, world");
+        assert_eq!(case_fold("Straße"), case_fold("STRASSE"));
+        assert_eq!(case_fold("ΣΊΣΥΦΟΣ"), case_fold("σίσυφος"));
+    }
+
+    #[test]
+    fn test_recursive() {
+        let temp = temp_dir("recursive");
+        let a = write_temp_file(&temp, "a.txt", "one\n");
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("b.txt"), "two three\n").unwrap();
+        fs::write(dir.join("sub").join("c.md"), "four\n").unwrap();
+        let dir = dir.to_str().unwrap().to_owned();
+
+        let cli = Cli { recursive: true, filenames: vec![dir.clone()], ..Default::default() };
+        let results = run_structured(cli).unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].filename, Some(a));
+        assert_eq!(results[3].words, Some(4));
+
+        let cli = Cli {
+            recursive: true,
+            include: vec!["*.txt".to_owned()],
+            exclude: vec!["a*".to_owned()],
+            filenames: vec![dir.clone()],
+            ..Default::default()
+        };
+        let results = run_structured(cli).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].words, Some(2));
+
+        let cli = Cli {
+            recursive: true,
+            include: vec!["[".to_owned()],
+            filenames: vec![dir],
+            ..Default::default()
+        };
+        assert!(matches!(run_structured(cli), Err(CcwcError::InvalidArguments(_))));
+    }
+
+    #[test]
+    fn test_recursive_max_files() {
+        let temp = temp_dir("recursive-max-files");
+        let a = write_temp_file(&temp, "a.txt", "one\n");
+        let dir = temp.path();
+        fs::write(dir.join("b.txt"), "two three\n").unwrap();
+        fs::write(dir.join("c.txt"), "four five six\n").unwrap();
+        let dir = dir.to_str().unwrap().to_owned();
+
+        let cli = Cli { recursive: true, max_files: Some(2), filenames: vec![dir], ..Default::default() };
+        let results = run_structured(cli).unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].filename, Some(a));
+        assert_eq!(results[2].filename.as_deref(), Some("total"));
+        assert_eq!(results[2].words, Some(3));
+    }
+
+    #[test]
+    fn test_sort_by() {
+        let temp = temp_dir("sort-by");
+        let a = write_temp_file(&temp, "a.txt", "one two three\n");
+        let b = write_temp_file(&temp, "b.txt", "four\nfive\n");
+        let c = write_temp_file(&temp, "c.txt", "six seven\n");
+        let filenames = |sort_by, sort_reverse| {
+            let cli = Cli {
+                sort_by: Some(sort_by),
+                sort_reverse,
+                filenames: vec![a.clone(), b.clone(), c.clone()],
+                ..Default::default()
+            };
+            run_structured(cli)
+                .unwrap()
+                .into_iter()
+                .map(|result| result.filename.unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(filenames(SortField::Words, false), vec![&b, &c, &a, "total"]);
+        assert_eq!(filenames(SortField::Words, true), vec![&a, &c, &b, "total"]);
+        assert_eq!(filenames(SortField::Lines, true), vec![&b, &c, &a, "total"]);
+        assert_eq!(filenames(SortField::Filename, true), vec![&c, &b, &a, "total"]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_run_async() {
+        let temp = temp_dir("run-async");
+        let a = write_temp_file(&temp, "a.txt", "hello, world\n");
+        let b = write_temp_file(&temp, "b.txt", "one\ntwo three\n");
+
+        let cli = Cli { filenames: vec![a.clone()], ..Default::default() };
+        let expected = CountResult {
+            lines: Some(1),
+            words: Some(2),
+            bytes: Some(13),
+            filename: Some(a.clone()),
+            ..Default::default()
+        };
+        assert_eq!(run_async(cli).await.unwrap(), vec![expected]);
+
+        let cli = || Cli { filenames: vec![a.clone(), b.clone()], ..Default::default() };
+        let results = run_async(cli()).await.unwrap();
+        assert_eq!(results, run_structured(cli()).unwrap());
+        assert_eq!(results.len(), 3);
+        let expected = CountResult {
+            lines: Some(3),
+            words: Some(5),
+            bytes: Some(27),
+            filename: Some("total".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(results[2], expected);
+
+        let cli = Cli {
+            filenames: vec![a.clone(), b.clone()],
+            total: TotalMode::Never,
+            sort_by: Some(SortField::Words),
+            sort_reverse: true,
+            ..Default::default()
+        };
+        let results = run_async(cli).await.unwrap();
+        let filenames: Vec<_> = results.iter().map(|r| r.filename.clone().unwrap()).collect();
+        assert_eq!(filenames, vec![b.clone(), a.clone()]);
+
+        let cli = Cli { filenames: vec![a, b], min_lines: Some(2), ..Default::default() };
+        let results = run_async(cli).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].filename.as_deref(), Some("total"));
+
+        let binary = write_temp_file(&temp, "c.bin", "\0\0\0");
+        let cli = Cli { filenames: vec![binary], skip_binary: true, ..Default::default() };
+        assert_eq!(run_async(cli).await.unwrap(), vec![]);
+
+        let cli = Cli { filenames: vec!["missing.txt".to_owned()], ..Default::default() };
+        assert!(matches!(run_async(cli).await, Err(CcwcError::FileNotFound(_))));
+    }
+
+    #[test]
+    fn test_run_with_reader() {
+        let reader = io::Cursor::new(b"hello, world\n".as_slice());
+        let result = run_with_reader(Cli::default(), reader).unwrap();
+        let expected =
+            CountResult { lines: Some(1), words: Some(2), bytes: Some(13), ..Default::default() };
+        assert_eq!(result, expected);
+
+        let cli = Cli {
+            char_count: true,
+            limit: Some(7),
+            stdin_label: Some("<input>".to_owned()),
+            ..Default::default()
+        };
+        let result = run_with_reader(cli, io::Cursor::new("こんにちは".as_bytes())).unwrap();
+        assert_eq!(result.chars, Some(2));
+        assert_eq!(result.filename.as_deref(), Some("<input>"));
+
+        let cli = Cli { encoding: Encoding::Utf16Le, ..Default::default() };
+        assert!(matches!(
+            run_with_reader(cli, io::Cursor::new(b"a".as_slice())),
+            Err(CcwcError::InvalidEncoding(Encoding::Utf16Le))
+        ));
+    }
+
+    #[test]
+    fn test_run_quiet() {
+        let temp = temp_dir("run-quiet");
+        let a = write_temp_file(&temp, "a.txt", "one two\n");
+        let b = write_temp_file(&temp, "b.txt", "");
+        let quiet = |cli: Cli| run_quiet(Cli { quiet: true, ..cli });
+
+        let filenames = vec![a.clone()];
+        assert!(quiet(Cli { line_count: true, filenames, ..Default::default() }).unwrap());
+        let filenames = vec![b.clone()];
+        assert!(!quiet(Cli { line_count: true, filenames, ..Default::default() }).unwrap());
+        let filenames = vec![a.clone(), b.clone()];
+        assert!(quiet(Cli { word_count: true, filenames, ..Default::default() }).unwrap());
+        let filenames = vec![a.clone()];
+        assert!(!quiet(Cli { tab_count: true, filenames, ..Default::default() }).unwrap());
+        let filenames = vec![b.clone()];
+        assert!(!quiet(Cli { average_word_length: true, filenames, ..Default::default() }).unwrap());
+
+        for cli in [
+            Cli { filenames: vec![a.clone()], ..Default::default() },
+            Cli { line_count: true, word_count: true, filenames: vec![a], ..Default::default() },
+        ] {
+            assert!(matches!(quiet(cli), Err(CcwcError::InvalidArguments(_))));
+        }
+    }
+
+    #[test]
+    fn test_assert_bounds() {
+        let temp = temp_dir("assert-bounds");
+        let a = write_temp_file(&temp, "a.txt", "one\ntwo\n");
+        let b = write_temp_file(&temp, "b.txt", "three\n");
+        let cli = |assert_min, assert_max| Cli {
+            line_count: true,
+            assert_min,
+            assert_max,
+            filenames: vec![a.clone(), b.clone()],
+            ..Default::default()
+        };
+
+        // The total of 3 lines is not checked against the maximum
+        assert_eq!(run_structured(cli(Some(1), Some(2))).unwrap().len(), 3);
+        match run_structured(cli(Some(2), None)) {
+            Err(CcwcError::CountOutOfBounds(message)) => {
+                assert_eq!(message, format!("{b}: lines is 1, less than the minimum of 2"));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        match run_structured(cli(None, Some(1))) {
+            Err(CcwcError::CountOutOfBounds(message)) => {
+                assert_eq!(message, format!("{a}: lines is 2, more than the maximum of 1"));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+
+        let cli = Cli {
+            average_word_length: true,
+            assert_max: Some(2),
+            filenames: vec![a.clone()],
+            ..Default::default()
+        };
+        match run_structured(cli) {
+            Err(CcwcError::CountOutOfBounds(message)) => {
+                assert_eq!(message, format!("{a}: average_word_length is 3.00, more than the maximum of 2"));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+
+        let cli = Cli { assert_max: Some(10), filenames: vec![a], ..Default::default() };
+        assert!(matches!(run_structured(cli), Err(CcwcError::InvalidArguments(_))));
+    }
+
+    #[test]
+    fn test_count_limits() {
+        let temp = temp_dir("count-limits");
+        let a = write_temp_file(&temp, "a.txt", "one two three\n");
+        let b = write_temp_file(&temp, "b.txt", "four\nfive\n");
+        let c = write_temp_file(&temp, "c.txt", "six seven\n");
+        let filenames = vec![a.clone(), b.clone(), c.clone()];
+
+        let cli = Cli { min_lines: Some(2), filenames: filenames.clone(), ..Default::default() };
+        let results = run_structured(cli).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].filename, Some(b));
+        assert_eq!(results[1].words, Some(7));
+
+        let cli = Cli {
+            min_words: Some(2),
+            max_lines: Some(1),
+            max_bytes: Some(12),
+            filenames: filenames.clone(),
+            ..Default::default()
+        };
+        let results = run_structured(cli).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].filename, Some(c));
+
+        let cli = Cli {
+            max_lines: Some(0),
+            total: TotalMode::Never,
+            filenames,
+            ..Default::default()
+        };
+        assert!(run_structured(cli).unwrap().is_empty());
+
+        let cli = Cli { word_count: true, max_lines: Some(0), filenames: vec![a], ..Default::default() };
+        assert_eq!(run_structured(cli).unwrap().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_recursive_no_follow_symlinks() {
+        let temp = temp_dir("recursive-symlinks");
+        let a = write_temp_file(&temp, "a.txt", "one\n");
+        let dir = temp.path();
+        std::os::unix::fs::symlink(&a, dir.join("link.txt")).unwrap();
+        let dir = dir.to_str().unwrap().to_owned();
+
+        let cli = Cli { recursive: true, filenames: vec![dir.clone()], ..Default::default() };
+        assert_eq!(run_structured(cli).unwrap().len(), 3);
+
+        let cli = Cli {
+            recursive: true,
+            no_follow_symlinks: true,
+            filenames: vec![dir],
+            ..Default::default()
+        };
+        assert_eq!(run_structured(cli).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_process_file_list() {
+        let temp = temp_dir("process-file-list");
+        let a = write_temp_file(&temp, "a.txt", "one\n");
+        let b = write_temp_file(&temp, "b.txt", "two three\n");
+        let config = CountConfig::builder().words(true).build();
+
+        let results = process_file_list(vec![b.clone(), a.clone()].into_iter(), &config).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!((results[0].filename.clone(), results[0].words), (Some(b), Some(2)));
+        assert_eq!((results[1].filename.clone(), results[1].words), (Some(a.clone()), Some(1)));
+
+        let missing = vec![a, "missing.txt".to_owned()].into_iter();
+        assert!(process_file_list(missing, &config).is_err());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_count_file_mmap() {
+        let temp = temp_dir("mmap");
+        let path = write_temp_file(&temp, "a.txt", "hello, world\nこんにちは\n");
+        let config = CountConfig::builder().lines(true).chars(true).mmap_threshold(0).build();
+
+        let mapped = count_file_mmap(&config, &path).unwrap().unwrap().result();
+        assert_eq!(mapped.lines, Some(2));
+        assert_eq!(mapped.chars, Some(19));
+        assert_eq!(mapped, count_file(&config, path.clone()).unwrap().result());
+
+        let config = CountConfig::builder().lines(true).build();
+        assert!(count_file_mmap(&config, &path).unwrap().is_none());
+        assert!(count_file_mmap(&config, "missing.txt").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_append_to_csv() {
+        let temp = temp_dir("append");
+        let path = write_temp_file(&temp, "log.csv", "");
+        fs::remove_file(&path).unwrap();
+        let result = CountResult {
+            lines: Some(1),
+            words: Some(2),
+            filename: Some("a.txt".to_owned()),
+            ..Default::default()
+        };
+        let timestamp = DateTime::from_timestamp(1_705_312_800, 0).unwrap();
+
+        append_to_csv(&path, &result, timestamp).unwrap();
+        append_to_csv(&path, &result, timestamp).unwrap();
+        append_to_csv(&path, &result, timestamp).unwrap();
+
+        let row = "2024-01-15T10:00:00Z,a.txt,1,2,,,,,,,,,,,,,,,,,,,,,";
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("timestamp,{CSV_HEADER}\n{row}\n{row}\n{row}\n")
+        );
+
+        // An empty file gets a header, and a row without a newline is ended
+        let path = write_temp_file(&temp, "empty.csv", "");
+        append_to_csv(&path, &result, timestamp).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("timestamp,{CSV_HEADER}\n{row}\n"));
+        let path = write_temp_file(&temp, "unterminated.csv", "timestamp,filename");
+        append_to_csv(&path, &result, timestamp).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("timestamp,filename\n{row}\n"));
+        assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_check() {
+        let temp = temp_dir("check");
+        let a = write_temp_file(&temp, "a.txt", "one two\nthree\n");
+        let b = write_temp_file(&temp, "b c.txt", "four\n");
+        let missing = a.replace("a.txt", "missing.txt");
+        let counts = write_temp_file(
+            &temp,
+            "counts.txt",
+            &format!("   2   3 {a}\n   1   9 {b}\n   0   0 {missing}\n\n   3   4 total\n"),
+        );
+        let cli = Cli {
+            line_count: true,
+            word_count: true,
+            check: Some(counts.clone()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            check(&cli).unwrap(),
+            [
+                CheckOutcome { filename: a.clone(), matches: true, error: None },
+                CheckOutcome { filename: b.clone(), matches: false, error: None },
+                CheckOutcome {
+                    filename: missing.clone(),
+                    matches: false,
+                    error: Some(format!("{missing}: No such file or directory")),
+                },
+            ]
+        );
+        assert_eq!(check(&cli).unwrap()[1].to_string(), format!("{b}: FAILED"));
+        assert_eq!(check(&cli).unwrap()[2].to_string(), format!("{missing}: FAILED open or read"));
+
+        fs::write(&counts, "   2 missing counts\n").unwrap();
+        let cli = Cli {
+            line_count: true,
+            word_count: true,
+            byte_count: true,
+            check: Some(counts),
+            ..Default::default()
+        };
+        assert!(matches!(check(&cli), Err(CcwcError::InvalidArguments(_))));
+    }
+
+    #[test]
+    fn test_run_to() {
+        let temp = temp_dir("run-to");
+        let path = write_temp_file(&temp, "a.txt", "one two\n");
+        let cli = Cli { word_count: true, filenames: vec![path.clone()], ..Default::default() };
+        let mut output = Vec::new();
+
+        run_to(cli, &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), format!("   2 {path}\n"));
+    }
+
+    #[test]
+    fn test_diff() {
+        let temp = temp_dir("diff");
+        let before = write_temp_file(&temp, "before.txt", "one two\nthree\n");
+        let after = write_temp_file(&temp, "after.txt", "one\n");
+        let cli = Cli {
+            line_count: true,
+            word_count: true,
+            diff: Some(vec![before.clone(), after.clone()]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            run(cli).unwrap(),
+            format!("--- {before}\n+++ {after}\nlines: 2 -> 1 (-1)\nwords: 3 -> 1 (-2)")
+        );
+    }
+
+    #[test]
+    fn test_diff_results() {
+        let before = CountResult { lines: Some(100), bytes: Some(7), ..Default::default() };
+        let after = CountResult { lines: Some(150), bytes: Some(7), ..Default::default() };
+        let diff = diff_results(&before, &after);
+
+        assert_eq!(diff.lines, Some(CountDelta { before: 100, after: 150, change: 50 }));
+        assert_eq!(diff.bytes.unwrap().change, 0);
+        assert_eq!(diff.words, None);
+        assert_eq!(
+            diff.to_string(),
+            "--- before\n+++ after\nlines: 100 -> 150 (+50)\nbytes: 7 -> 7 (+0)"
+        );
+    }
+
+    #[test]
+    fn test_is_likely_binary() {
+        assert!(!is_likely_binary(b""));
+        assert!(!is_likely_binary("hello, world\nこんにちは\n".as_bytes()));
+        assert!(is_likely_binary(b"\x7fELF\x02\x01\x01\x00\x00"));
+
+        let mut late_nul = vec![b'a'; 8 * 1024];
+        late_nul.push(0);
+        assert!(!is_likely_binary(&late_nul));
+    }
+
+    #[test]
+    fn test_count_bytes_from_metadata() {
+        let temp = temp_dir("bytes-from-metadata");
+        let path = write_temp_file(&temp, "a.txt", "\u{feff}こんにちは\n");
+        assert_eq!(count_bytes_from_metadata(Path::new(&path)).unwrap(), 19);
+
+        let config = CountConfig::for_bytes();
+        let counter = count_file_from_metadata(&config, &path).unwrap();
+        let read = CountConfig { filename: Some(path.clone()), ..config.clone() };
+        let read = Counter::new(read).count_raw(fs::read(&path).unwrap()).unwrap();
+        assert_eq!(counter, read);
+
+        assert!(count_file_from_metadata(&CountConfig::for_chars(), &path).is_none());
+        assert!(count_file_from_metadata(&CountConfig::default(), &path).is_none());
+        let empty = write_temp_file(&temp, "empty.txt", "");
+        assert!(count_file_from_metadata(&config, &empty).is_none());
+        assert!(count_bytes_from_metadata(&Path::new(&path).with_file_name("missing.txt")).is_err());
+    }
+
+    #[test]
+    fn test_strip_bom() {
+        assert_eq!(strip_bom(b"\xEF\xBB\xBFone\n"), b"one\n");
+        assert_eq!(strip_bom(b"one\n"), b"one\n");
+        assert_eq!(strip_bom(b"\xEF\xBB"), b"\xEF\xBB");
+
+        let input = b"\xEF\xBB\xBFone two\n".to_vec();
+        let count = |config: CountConfigBuilder| {
+            Counter::new(config.build()).count_raw(input.clone()).unwrap().result()
+        };
+        assert_eq!(count(CountConfig::builder().chars(true)).chars, Some(9));
+        assert_eq!(count(CountConfig::builder().chars(true).strip_bom(true)).chars, Some(8));
+        assert_eq!(count(CountConfig::builder().bytes(true)).bytes, Some(11));
+        assert_eq!(count(CountConfig::builder().bytes(true).strip_bom(true)).bytes, Some(8));
+    }
+
+    #[test]
+    fn test_skip_binary() {
+        let temp = temp_dir("skip-binary");
+        let text = write_temp_file(&temp, "a.txt", "one two\n");
+        let binary = write_temp_file(&temp, "b.bin", "\0\x01\x02 three\n");
+        let cli = Cli {
+            word_count: true,
+            skip_binary: true,
+            filenames: vec![text.clone(), binary.clone()],
+            ..Default::default()
+        };
+
+        let results = run_structured(cli).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].filename, Some(text.clone()));
+        assert_eq!(results[1].filename, Some("total".to_owned()));
+        assert_eq!(results[1].words, Some(2));
+
+        let cli = Cli { word_count: true, filenames: vec![text, binary], ..Default::default() };
+        assert_eq!(run_structured(cli).unwrap()[2].words, Some(4));
+
+        // A NUL byte after the start that is checked does not make a file
+        // binary, and the whole of the file is still counted
+        let late_nul = format!("{} two\0 three\n", "a".repeat(BINARY_CHECK_SIZE));
+        let late_nul = write_temp_file(&temp, "c.txt", &late_nul);
+        let config = CountConfig::builder().words(true).skip_binary(true).build();
+        assert_eq!(count_file(&config, late_nul).unwrap().result().words, Some(3));
+    }
 
-/// A struct to hold the counts of bytes or characters, words, and lines in a file and the filename
-/// Each of the counts is an optional usize and the filename is a required string
-#[derive(Debug)]
-struct Counter {
-    config: CountConfig,
-    byte_or_char_count: Option<usize>,
-    word_count: Option<usize>,
-    line_count: Option<usize>,
-}
+    #[test]
+    fn test_progress_reader() {
+        let input = vec![b'a'; 10_000];
+        let mut reader = ProgressReader::new(&input[..], "a.txt", input.len() as u64);
+        let mut contents = Vec::new();
 
-impl Counter {
-    /// A function to create a new Counter struct with the given filename
-    /// and all counts set to None
-    fn new(config: CountConfig) -> Counter {
-        Counter {
-            config,
-            byte_or_char_count: None,
-            word_count: None,
-            line_count: None,
-        }
+        reader.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, input);
+        assert_eq!(reader.read, 10_000);
+        assert!(reader.last_printed.is_some());
     }
 
-    /// Actually calculates the counts specified in the config of the Counter.
-    /// Mutates the Counter to add the counts to it.
-    fn count(mut self, contents: &String) -> Counter {
-        match self.config.count_chars {
-            CharCount::Chars => self.byte_or_char_count = Some(count_characters(contents)),
-            CharCount::Bytes => self.byte_or_char_count = Some(count_bytes(contents)),
-            CharCount::None => self.byte_or_char_count = None,
-        }
+    #[test]
+    fn test_progress_mode() {
+        let config = CountConfig::from_cli(&Cli { progress: true, ..Default::default() });
+        assert_eq!(config.progress_threshold(), Some(DEFAULT_PROGRESS_THRESHOLD));
 
-        if self.config.count_lines {
-            self.line_count = Some(count_lines(contents));
-        }
+        let config = CountConfig::builder().progress(false).build();
+        assert_eq!(config.progress_threshold(), None);
+    }
 
-        if self.config.count_words {
-            self.word_count = Some(count_words(contents));
-        }
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("0"), Ok(0));
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("64K"), Ok(64 * 1024));
+        assert_eq!(parse_size("1M"), Ok(1024 * 1024));
+        assert_eq!(parse_size("2G"), Ok(2 * 1024 * 1024 * 1024));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("K").is_err());
+        assert!(parse_size("1.5M").is_err());
+        assert!(parse_size("99999999999999G").is_err());
+    }
 
-        self
+    #[test]
+    fn test_limit() {
+        let temp = temp_dir("limit");
+        let path = write_temp_file(&temp, "a.txt", "hello, world\nsecond line\n");
+        let cli = Cli { limit: Some(10), filenames: vec![path.clone()], ..Default::default() };
+        let result = &run_structured(cli).unwrap()[0];
+
+        assert_eq!(result.bytes, Some(10));
+        assert_eq!(result.words, Some(2));
+        assert_eq!(result.lines, Some(0));
+
+        let cli = Cli { limit: Some(1000), filenames: vec![path], ..Default::default() };
+        assert_eq!(run_structured(cli).unwrap()[0].bytes, Some(25));
     }
 
-    /// A function to create a formatted output string from the Counter struct
-    /// The output string is formatted as follows:
-    /// line_count word_count byte_count filename
-    /// where each count is right-aligned in a column of width a multiple of 4
-    /// and each column is separated by a space
-    fn as_string(&self) -> String {
-        let mut output = String::new();
+    #[test]
+    fn test_limit_splits_character() {
+        let config = CountConfig::builder().chars(true).limit(Some(6)).build();
+        let result = count_streaming("aこんにちは".as_bytes(), &config).unwrap();
+        assert_eq!(result.chars, Some(2));
 
-        if let Some(line_count) = self.line_count {
-            output.push_str(&format_output(line_count.to_string()));
-        }
-        if let Some(word_count) = self.word_count {
-            output.push_str(&format_output(word_count.to_string()));
-        }
-        if let Some(byte_count) = self.byte_or_char_count {
-            output.push_str(&format_output(byte_count.to_string()));
-        }
+        let config = CountConfig::builder().bytes(true).limit(Some(6)).build();
+        assert_eq!(count_streaming("aこんにちは".as_bytes(), &config).unwrap().bytes, Some(6));
+        assert_eq!(count_streaming("abcdefghij".as_bytes(), &config).unwrap().bytes, Some(6));
+    }
 
-        if let Some(filename) = &self.config.filename {
-            output.push_str(&format!(" {}",filename));
-        }
+    #[test]
+    fn test_word_frequency() {
+        let frequency = word_frequency("b a c\nb a\nb d");
 
-        output
+        assert_eq!(frequency.len(), 4);
+        assert_eq!(frequency["a"], 2);
+        assert_eq!(frequency["b"], 3);
+        assert_eq!(frequency["d"], 1);
+        assert!(word_frequency(" \n").is_empty());
     }
-}
 
-/// Formats a string so that it is right-aligned in a column of width a multiple of 4
-fn format_output(input_string: String) -> String {
-    let column_width: usize = 4 *((input_string.len() / 4) + 1);
-    format!("{input_string: >column_width$}", column_width=column_width)
-}
+    #[test]
+    fn test_format_frequency_order() {
+        let frequency = word_frequency("d b a c\nb a\nb");
 
-/// Count the number of bytes in a string
-fn count_bytes(input_string: &str) -> usize {
-    input_string.len()
-}
+        assert_eq!(format_frequency(frequency.clone(), None), "3\tb\n2\ta\n1\tc\n1\td");
+        assert_eq!(format_frequency(frequency.clone(), Some(3)), "3\tb\n2\ta\n1\tc");
+        assert_eq!(format_frequency(frequency, Some(0)), "");
+    }
 
-/// Count the number of characters in a string
-fn count_characters(input_string: &str) -> usize {
-    input_string.chars().count()
-}
+    #[test]
+    fn test_run_frequency() {
+        let temp = temp_dir("frequency");
+        let a = write_temp_file(&temp, "a.txt", "The cat\n");
+        let b = write_temp_file(&temp, "b.txt", "the hat\n");
+        let cli = Cli {
+            frequency: true,
+            ignore_case: true,
+            top: Some(2),
+            filenames: vec![a, b],
+            ..Default::default()
+        };
 
-/// Count the number of lines in a string
-fn count_lines(input_string: &str) -> usize {
-    input_string.lines().count()
-}
+        assert_eq!(run(cli).unwrap(), "2\tthe\n1\tcat");
 
-/// Count the number of words in a string
-fn count_words(input_string: &str) -> usize {
-    input_string.split_whitespace().count()
-}
+        let c = write_temp_file(&temp, "c.sh", "echo note # note note\n# note\n");
+        let cli = Cli {
+            frequency: true,
+            comment_strip: Some(CommentStyle::Hash),
+            filenames: vec![c],
+            ..Default::default()
+        };
+        assert_eq!(run(cli).unwrap(), "1\techo\n1\tnote");
 
-/// The public interface to the library. Takes in a Cli struct and runs the
-/// counts specified therein reading from a file or stdin as required.
-pub fn run(cli: Cli) -> Result<String, Box<dyn Error>>{
-    let mut contents = String::new();
+        let d = write_temp_file(&temp, "d.txt", "");
+        fs::write(&d, b"caf\xE9 caf\xE9\n").unwrap();
+        let cli = Cli { frequency: true, encoding_detect: true, filenames: vec![d], ..Default::default() };
+        assert_eq!(run(cli).unwrap(), "2\tcafé");
 
-    match &cli.filename {
-        Some(filename) => {
-            contents = fs::read_to_string(filename)?;
-        }
-        None => {
-            io::stdin().read_to_string(&mut contents)?;
-        }
+        let e = write_temp_file(&temp, "e.txt", "\u{FEFF}one two one\n");
+        let cli = || Cli { frequency: true, filenames: vec![e.clone()], ..Default::default() };
+        let limited = Cli { limit: Some(10), strip_bom: true, ..cli() };
+        assert_eq!(run(limited).unwrap(), "1\tone\n1\ttwo");
+        assert_eq!(run(Cli { strip_bom: true, ..cli() }).unwrap(), "2\tone\n1\ttwo");
+        assert_eq!(run(cli()).unwrap(), "1\tone\n1\ttwo\n1\t\u{FEFF}one");
+
+        let binary = write_temp_file(&temp, "f.bin", "one\0");
+        let cli = Cli { frequency: true, skip_binary: true, filenames: vec![binary], ..Default::default() };
+        assert_eq!(run(cli).unwrap(), "");
     }
 
-    let count_config = CountConfig::from_cli(&cli);
-    let counter = Counter::new(count_config).count(&contents);
+    #[test]
+    fn test_count_unique_lines() {
+        assert_eq!(count_unique_lines(""), 0);
+        assert_eq!(count_unique_lines("a\nb\na\n"), 2);
+        assert_eq!(count_unique_lines("a\r\na\n\n\n"), 2);
+    }
 
-    Ok(counter.as_string())
-}
+    #[test]
+    fn test_count_result_from_str() {
+        let result = CountResult::from("");
+        let counts = (result.lines, result.words, result.bytes, result.chars);
+        assert_eq!(counts, (Some(0), Some(0), Some(0), Some(0)));
+        assert_eq!(result.filename, None);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let input = "hello, world\nこんにちは 世界\n\ttabbed  line";
+        let result = CountResult::from(input);
+        let counts = (result.lines, result.words, result.bytes, result.chars);
+        assert_eq!(counts, (Some(2), Some(6), Some(49), Some(35)));
+        assert_eq!(result.max_line_length, None);
+        assert_eq!(CountResult::from(input.to_owned()), result);
+    }
 
     #[test]
-    fn test_counter_byte_count() {
+    fn test_count_duplicate_lines() {
+        // No duplicates
+        assert_eq!(count_duplicate_lines(""), 0);
+        assert_eq!(count_duplicate_lines("a\nb\nc"), 0);
+        // All duplicates of the first line
+        assert_eq!(count_duplicate_lines("a\na\na\n"), 2);
+        assert_eq!(count_duplicate_lines("\n\n"), 1);
+        // Some duplicates, with CRLF line endings matching LF ones
+        assert_eq!(count_duplicate_lines("a\nb\na\nc\nb\na\n"), 3);
+        assert_eq!(count_duplicate_lines("a\r\na\nA\n"), 1);
+    }
+
+    #[test]
+    fn test_counter_duplicate_lines() {
+        let input = "Error\nerror\nwarning\nError\n";
+        let config = CountConfig::builder().duplicate_lines(true).build();
+        let result = Counter::new(config).count(input).result();
+        assert_eq!(result.duplicate_lines, Some(1));
+        assert_eq!(result.unique_lines, None);
+
+        let config =
+            CountConfig::builder().unique_lines(true).duplicate_lines(true).ignore_case(true).build();
+        let result = Counter::new(config).count(input).result();
+        assert_eq!(result.unique_lines, Some(2));
+        assert_eq!(result.duplicate_lines, Some(2));
+    }
+
+    #[test]
+    fn test_counter_unique_lines_ignore_case() {
         let config = CountConfig::from_cli(
             &Cli {
-                filename: None,
-                byte_count: true,
-                char_count: false,
-                line_count: false,
-                word_count: false,
+                unique_lines: true,
+                ignore_case: true,
+                ..Default::default()
             }
         );
-        let result = Counter::new(config).count(&"hello, world".to_owned());
+        let result = Counter::new(config).count("Error: disk\nerror: DISK\nwarning\n");
 
-        assert_eq!(
-            result.byte_or_char_count.unwrap(),
-            12
-        );
-        assert_eq!(
-            result.line_count,
-            None
-        );
-        assert_eq!(
-            result.word_count,
-            None
-        );
+        assert_eq!(result.unique_line_count.unwrap(), 2);
+        assert_eq!(result.unique_word_count, None);
     }
 
     #[test]
-    fn test_counter_char_count() {
+    fn test_max_word_length() {
+        assert_eq!(max_word_length(""), 0);
+        assert_eq!(max_word_length("a bb\nccc"), 3);
+        assert_eq!(max_word_length("こんにちは world"), 5);
+    }
+
+    #[test]
+    fn test_counter_max_word_length() {
         let config = CountConfig::from_cli(
             &Cli {
-                filename: None,
-                byte_count: false,
-                char_count: true,
-                line_count: false,
-                word_count: false,
+                max_word_length: true,
+                ..Default::default()
             }
         );
-        let result = Counter::new(config).count(&"hello, world".to_owned());
+        let result = Counter::new(config).count("the longest word\n").result();
 
         assert_eq!(
-            result.byte_or_char_count.unwrap(),
-            12
-        );
-        assert_eq!(
-            result.line_count,
-            None
+            result,
+            CountResult {
+                max_word_length: Some(7),
+                ..Default::default()
+            }
         );
-        assert_eq!(
-            result.word_count,
-            None
+    }
+
+    #[test]
+    fn test_count_paragraphs_no_blank_lines() {
+        assert_eq!(count_paragraphs(""), 0);
+        assert_eq!(count_paragraphs("one\ntwo\nthree\n"), 1);
+    }
+
+    #[test]
+    fn test_count_paragraphs_trailing_blank_lines() {
+        assert_eq!(count_paragraphs("one\ntwo\n\n\n"), 1);
+        assert_eq!(count_paragraphs("\n\none\n\ntwo\n  \n"), 2);
+    }
+
+    #[test]
+    fn test_count_paragraphs_consecutive_blank_lines() {
+        assert_eq!(count_paragraphs("one\n\n\n\ntwo\n \t\n\nthree"), 3);
+    }
+
+    #[test]
+    fn test_counter_paragraph_count() {
+        let config = CountConfig::from_cli(
+            &Cli {
+                paragraph_count: true,
+                ..Default::default()
+            }
         );
+        let result = Counter::new(config).count("First paragraph.\n\nSecond\nparagraph.\n");
+
+        assert_eq!(result.paragraph_count.unwrap(), 2);
+        assert_eq!(result.line_count, None);
     }
 
     #[test]
-    fn test_counter_line_count() {
+    fn test_count_fields() {
+        assert_eq!(count_fields("", ','), 0);
+        assert_eq!(count_fields("a,b,c\n1,2,3\n", ','), 6);
+        assert_eq!(count_fields("a,b\n\nc\r\n", ','), 4);
+        assert_eq!(count_fields("a\tb c\td", '\t'), 3);
+
+        let cli = Cli::parse_from(["ccwc", "-lw", "--delimiter", ","]);
+        let config = CountConfig::from_cli(&cli);
+        let input = "name,age\nAda Lovelace,36\n";
+        let result = Counter::new(config.clone()).count(input).result();
+        assert_eq!(result.words, Some(4));
+        assert_eq!(result.lines, Some(2));
+        assert_eq!(count_streaming(input.as_bytes(), &config).unwrap(), result);
+        assert_eq!(config, CountConfig::builder().lines(true).words(true).delimiter(Some(',')).build());
+        assert!(Cli::try_parse_from(["ccwc", "--delimiter", "ab"]).is_err());
+    }
+
+    #[test]
+    fn test_count_non_blank_lines() {
+        assert_eq!(count_non_blank_lines(""), 0);
+        assert_eq!(count_non_blank_lines("\n \none\ntwo\n"), 2);
+        assert_eq!(count_non_blank_lines("one\n\t\n\ntwo\n"), 2);
+        assert_eq!(count_non_blank_lines("one\ntwo\n\n  \n"), 2);
+        assert_eq!(count_non_blank_lines("one\r\n\r\ntwo"), 2);
+    }
+
+    #[test]
+    fn test_counter_non_blank_lines_and_lines() {
         let config = CountConfig::from_cli(
             &Cli {
-                filename: None,
-                byte_count: false,
-                char_count: false,
                 line_count: true,
-                word_count: false,
+                non_blank: true,
+                ..Default::default()
             }
         );
-        let result = Counter::new(config).count(&"hello, world".to_owned());
+        let result = Counter::new(config).count("\nfirst\n\nsecond\n\n").result();
 
-        assert_eq!(
-            result.byte_or_char_count,
-            None
-        );
-        assert_eq!(
-            result.line_count.unwrap(),
-            1
+        assert_eq!(result.lines, Some(5));
+        assert_eq!(result.non_blank_lines, Some(2));
+        assert_eq!(result.to_string(), "   5   2");
+    }
+
+    #[test]
+    fn test_count_sentences() {
+        assert_eq!(count_sentences(""), 0);
+        assert_eq!(count_sentences("no full stop"), 0);
+        assert_eq!(count_sentences("Mr. Smith went to Washington."), 1);
+        assert_eq!(count_sentences("Dr. J. Smith, Prof. Jones etc. arrived. They left."), 2);
+        assert_eq!(count_sentences("Really?! Yes... I think so."), 3);
+        assert_eq!(count_sentences("He said \"stop.\" She did (mostly.) Then?"), 3);
+        assert_eq!(count_sentences("Pi is 3.14 or so"), 0);
+        assert_eq!(count_sentences("Bring fruit, e.g. apples, pears etc."), 1);
+        assert_eq!(count_sentences("One.Two"), 0);
+    }
+
+    #[test]
+    fn test_counter_sentences_and_words() {
+        let config = CountConfig::from_cli(
+            &Cli {
+                word_count: true,
+                sentence_count: true,
+                ..Default::default()
+            }
         );
+        let result = Counter::new(config).count("Mr. Smith went to Washington.\nHe liked it!\n").result();
+
+        assert_eq!(result.words, Some(8));
+        assert_eq!(result.sentences, Some(2));
+        assert_eq!(result.to_string(), "   8   2");
+    }
+
+    #[test]
+    fn test_average_word_length() {
+        assert_eq!(average_word_length(""), 0.0);
+        assert_eq!(average_word_length(" \n\t"), 0.0);
+        assert_eq!(average_word_length("a b c d"), 1.0);
+        assert_eq!(average_word_length("abc def ghi\njkl"), 3.0);
+        assert_eq!(average_word_length("a bb ccc dddd"), 2.5);
+        assert_eq!(average_word_length("こんにちは world"), 5.0);
+    }
+
+    #[test]
+    fn test_average_word_length_total() {
+        let config = CountConfig::from_cli(&Cli { average_word_length: true, ..Default::default() });
+        let a = Counter::new(config.clone()).count("a bb").result();
+        let b = Counter::new(config).count("cccccc").result();
+
+        assert_eq!(a.to_string(), "    1.50");
+        assert_eq!((a + b).average_word_length, Some(Average { count: 3, mean: 3.0 }));
+        assert_eq!(Average::default().merge(Average::default()), Average::default());
+    }
+
+    #[test]
+    fn test_line_length_histogram() {
+        let buckets = |n| NonZeroUsize::new(n).unwrap();
+        assert_eq!(line_length_histogram("", buckets(2)).counts, vec![0, 0]);
+        assert_eq!(line_length_histogram("\n", buckets(2)).counts, vec![1, 0]);
+        assert_eq!(line_length_histogram("こんにちは\n0123456789\n\n\r\n", buckets(3)).counts, vec![3, 1, 0]);
+
+        let input = format!("a\n{}\n{}\n", "b".repeat(15), "c".repeat(150));
+        assert_eq!(line_length_histogram(&input, buckets(3)).counts, vec![1, 1, 1]);
+        assert_eq!(line_length_histogram(&input, buckets(1)).counts, vec![3]);
+        let histogram = line_length_histogram(&"x".repeat(5_000_000), buckets(2));
+        assert_eq!(histogram.counts, vec![0, 1]);
+
+        let nul_separated = format!("a\n{}\0b\0", "c".repeat(10));
+        let histogram = line_length_histogram_separated_by(&nul_separated, buckets(2), b'\0');
+        assert_eq!(histogram.counts, vec![1, 1]);
+
+        let merged = line_length_histogram("a\n", buckets(2)).merge(line_length_histogram(&input, buckets(2)));
+        assert_eq!(merged.counts, vec![2, 2]);
+    }
+
+    #[test]
+    fn test_format_histogram() {
+        let histogram = Histogram { bucket_size: 10, counts: vec![4, 2, 0] };
         assert_eq!(
-            result.word_count,
-            None
+            histogram.to_string(),
+            format!("  0-9 | {} 4\n10-19 | {} 2\n  20+ |  0", "#".repeat(40), "#".repeat(20))
         );
+        assert_eq!(Histogram { bucket_size: 10, counts: vec![0] }.to_string(), "0+ |  0");
     }
 
     #[test]
-    fn test_counter_word_count() {
+    fn test_average_line_length() {
+        assert_eq!(average_line_length(""), 0.0);
+        assert_eq!(average_line_length("\n"), 0.0);
+        assert_eq!(average_line_length("abc\ndef\n"), 3.0);
+        assert_eq!(average_line_length("a\r\nbcd\r\n\nefghij"), 2.5);
+        assert_eq!(average_line_length("こんにちは\nab\n"), 3.5);
+    }
+
+    #[test]
+    fn test_average_and_max_line_length() {
         let config = CountConfig::from_cli(
             &Cli {
-                filename: None,
-                byte_count: false,
-                char_count: false,
-                line_count: false,
-                word_count: true,
+                max_line_length: true,
+                average_line_length: true,
+                ..Default::default()
             }
         );
-        let result = Counter::new(config).count(&"hello, world".to_owned());
+        let result = Counter::new(config).count("ab\ncdef\n").result();
 
-        assert_eq!(
-            result.byte_or_char_count,
-            None
+        assert_eq!(result.max_line_length, Some(4));
+        assert_eq!(result.average_line_length, Some(Average { count: 2, mean: 3.0 }));
+        assert_eq!(result.to_string(), "       4    3.00");
+    }
+
+    #[test]
+    fn test_count_tabs_and_spaces() {
+        assert_eq!(count_tabs(""), 0);
+        assert_eq!(count_spaces(""), 0);
+        assert_eq!(count_tabs("\tfn main() {\n\t\tx\u{3000}y\n"), 3);
+        assert_eq!(count_spaces("\tfn main() {\n\t\tx\u{3000}y\n"), 2);
+    }
+
+    #[test]
+    fn test_count_control_chars() {
+        assert_eq!(count_control_chars(b""), 0);
+        assert_eq!(count_control_chars("plain text\tこんにちは\r\n".as_bytes()), 0);
+        assert_eq!(count_control_chars(b"\x00\x01\x1f\x20\x7e\x7f\x80"), 4);
+        assert_eq!(count_all_control_chars(b"a\tb\r\n\x07"), 4);
+
+        let input = "\x1b[31mred\x1b[0m\n";
+        let count = |config: CountConfigBuilder| {
+            Counter::new(config.build()).count(input).result().control_chars
+        };
+        let config = CountConfig::builder().control_chars(true);
+        assert_eq!(count(config.clone()), Some(2));
+        assert_eq!(count(config.include_common_controls(true)), Some(3));
+        assert_eq!(count(CountConfig::builder().lines(true)), None);
+    }
+
+    #[test]
+    fn test_count_non_ascii() {
+        // ASCII only
+        assert_eq!(count_non_ascii_bytes(b""), 0);
+        assert_eq!(count_non_ascii_bytes(b"hello, world\n\x7f"), 0);
+        assert_eq!(count_non_ascii_chars("hello, world\n\x7f"), 0);
+        // Mixed
+        assert_eq!(count_non_ascii_bytes("na\u{ef}ve \u{1f44d}".as_bytes()), 6);
+        assert_eq!(count_non_ascii_chars("na\u{ef}ve \u{1f44d}"), 2);
+        // All non-ASCII
+        assert_eq!(count_non_ascii_bytes("こんにちは".as_bytes()), 15);
+        assert_eq!(count_non_ascii_chars("こんにちは"), 5);
+
+        let input = b"caf\xe9 cr\xe8me\n".to_vec();
+        let count = |config: CountConfigBuilder| {
+            let counter = Counter::new(config.encoding(Encoding::Latin1).build());
+            counter.count_raw(input.clone()).unwrap().result().non_ascii
+        };
+        assert_eq!(count(CountConfig::builder().non_ascii(true)), Some(2));
+        assert_eq!(count(CountConfig::builder().non_ascii(true).non_ascii_bytes(true)), Some(4));
+    }
+
+    #[test]
+    fn test_count_crlf() {
+        // Pure UNIX line endings
+        assert_eq!(count_crlf(b""), 0);
+        assert_eq!(count_crlf(b"one\ntwo\n"), 0);
+        // Pure Windows line endings
+        assert_eq!(count_crlf(b"one\r\ntwo\r\n"), 2);
+        // Mixed line endings, and a lone carriage return
+        assert_eq!(count_crlf(b"one\r\ntwo\nthree\rfour\r\n"), 2);
+
+        let input = "one\r\ntwo\nthree\r\n";
+        for crlf in [false, true] {
+            let config = CountConfig::builder().lines(true).crlf_line_endings(true).crlf(crlf).build();
+            let result = Counter::new(config).count(input).result();
+            assert_eq!((result.lines, result.crlf), (Some(3), Some(2)));
+            assert_eq!(result.to_string(), "   3   2");
+        }
+    }
+
+    #[test]
+    fn test_counter_tabs_and_spaces() {
+        let config = CountConfig::from_cli(
+            &Cli {
+                tab_count: true,
+                space_count: true,
+                ..Default::default()
+            }
         );
+        let input = "    indented\n\tindented\n";
+        let result = Counter::new(config.clone()).count(input).result();
+
+        assert_eq!((result.tabs, result.spaces), (Some(1), Some(4)));
+        assert_eq!(result.lines, None);
+        assert_eq!(count_streaming_in_chunks(input.as_bytes(), &config, 3).unwrap(), result);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_count_lines_matching() {
+        let pattern = Regex::new("TODO|FIXME").unwrap();
+
+        assert_eq!(count_lines_matching("", &pattern), 0);
+        assert_eq!(count_lines_matching("// TODO\nok\n// FIXME TODO\nTODO", &pattern), 3);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_counter_lines_matching_and_lines() {
+        let cli = Cli::parse_from(["ccwc", "-l", "--lines-containing", "^a"]);
+        let result = Counter::new(CountConfig::from_cli(&cli)).count("ab\nba\naa\n").result();
+
+        assert_eq!(result.lines, Some(3));
+        assert_eq!(result.matching_lines, Some(2));
+        assert_eq!(result.to_string(), "   3   2");
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_count_words_by_regex() {
+        let alphabetic = Regex::new("[a-zA-Z]+").unwrap();
+        let numeric = Regex::new(r"\d+").unwrap();
+
+        assert_eq!(count_words_by_regex("", &alphabetic), 0);
+        assert_eq!(count_words_by_regex("it's 42 o'clock, 7am", &alphabetic), 5);
+        assert_eq!(count_words_by_regex("it's 42 o'clock, 7am", &numeric), 2);
+
+        let cli = Cli::parse_from(["ccwc", "-w", "--word-regex", r"\d+"]);
+        let config = CountConfig::from_cli(&cli);
+        let input = "1 2 three 45\n";
+        assert_eq!(Counter::new(config.clone()).count(input).result().words, Some(3));
+        assert_eq!(count_streaming(input.as_bytes(), &config).unwrap().words, Some(3));
+        let built = CountConfig::builder().words(true).word_pattern(Regex::new(r"\d+").ok()).build();
+        assert_eq!(config, built);
+        assert!(Cli::try_parse_from(["ccwc", "--word-regex", "a", "--delimiter", ","]).is_err());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_lines_containing_invalid_regex() {
+        let error = Cli::try_parse_from(["ccwc", "--lines-containing", "(unclosed"]).unwrap_err();
+
+        assert!(error.to_string().contains("unclosed group"));
+    }
+
+    #[test]
+    fn test_humanize_bytes_binary() {
+        assert_eq!(humanize_bytes(0, true), "0");
+        assert_eq!(humanize_bytes(1023, true), "1023");
+        assert_eq!(humanize_bytes(1024, true), "1.0KiB");
+        assert_eq!(humanize_bytes(1075, true), "1.0KiB");
+        assert_eq!(humanize_bytes(1076, true), "1.1KiB");
+        assert_eq!(humanize_bytes(1_048_575, true), "1.0MiB");
+        assert_eq!(humanize_bytes(1_048_576, true), "1.0MiB");
+        assert_eq!(humanize_bytes(5 * 1024 * 1024 * 1024, true), "5.0GiB");
+        assert_eq!(humanize_bytes(2048 * 1024 * 1024 * 1024 * 1024, true), "2048.0TiB");
+    }
+
+    #[test]
+    fn test_humanize_bytes_si() {
+        assert_eq!(humanize_bytes(999, false), "999");
+        assert_eq!(humanize_bytes(1000, false), "1.0kB");
+        assert_eq!(humanize_bytes(1024, false), "1.0kB");
+        assert_eq!(humanize_bytes(999_949, false), "999.9kB");
+        assert_eq!(humanize_bytes(999_950, false), "1.0MB");
+        assert_eq!(humanize_bytes(1_500_000_000, false), "1.5GB");
+    }
+
+    #[test]
+    fn test_format_human_readable() {
+        let cli = Cli { human_readable: true, no_color: true, ..Default::default() };
+        let result = CountResult {
+            lines: Some(2048),
+            bytes: Some(2048),
+            ..Default::default()
+        };
+
+        assert_eq!(format_all(&[result], &FormatOptions::from_cli(&cli)), vec!["    2048  2.0KiB"]);
+    }
+
+    #[test]
+    fn test_format_color() {
+        let result = |filename: &str| CountResult {
+            lines: Some(1),
+            words: Some(2),
+            filename: Some(filename.to_owned()),
+            ..Default::default()
+        };
+        let results = [result("a.txt"), result("total")];
+        let options = FormatOptions::from_cli(&Cli { color: ColorMode::Always, ..Default::default() });
+
         assert_eq!(
-            result.line_count,
-            None
+            format_all(&results, &options),
+            [
+                "\x1b[0m\x1b[34m   1\x1b[0m\x1b[0m\x1b[32m   2\x1b[0m\x1b[0m\x1b[1m a.txt\x1b[0m",
+                "\x1b[0m\x1b[1m\x1b[34m   1\x1b[0m\x1b[0m\x1b[1m\x1b[32m   2\x1b[0m\x1b[0m\x1b[1m total\x1b[0m",
+            ]
         );
-        assert_eq!(
-            result.word_count.unwrap(),
-            2
+
+        for cli in [
+            Cli { color: ColorMode::Never, ..Default::default() },
+            Cli { color: ColorMode::Always, no_color: true, ..Default::default() },
+        ] {
+            let output = format_all(&results, &FormatOptions::from_cli(&cli));
+            assert_eq!(output, ["   1   2 a.txt", "   1   2 total"]);
+        }
+        assert!(Cli::try_parse_from(["ccwc", "--color", "always", "--no-color"]).is_err());
+    }
+
+    #[test]
+    fn test_format_header() {
+        let result = |lines, bytes, filename: &str| CountResult {
+            lines: Some(lines),
+            bytes: Some(bytes),
+            filename: Some(filename.to_owned()),
+            ..Default::default()
+        };
+        let results = [result(1, 12, "a.txt"), result(2, 120, "total")];
+        let options = FormatOptions::from_cli(&Cli { header: true, no_color: true, ..Default::default() });
+
+        assert_eq!(format_header(&results, &options).unwrap(), "   lines   bytes filename");
+        assert_eq!(format_all(&results, &options), ["       1      12 a.txt", "       2     120 total"]);
+        assert_eq!(format_header(&[], &options), None);
+
+        let options = FormatOptions::from_cli(
+            &Cli { header: true, column_separator: ",".to_owned(), ..Default::default() }
         );
+        assert_eq!(format_header(&results, &options).unwrap(), "lines,bytes,filename");
+
+        let cli = Cli::parse_from(["ccwc", "--header", "--omit-header"]);
+        assert!(!FormatOptions::from_cli(&cli).header);
     }
 
     #[test]
-    fn test_count_bytes() {
-        assert_eq!(count_bytes(""), 0);
-        assert_eq!(count_bytes("Hello, world!"), 13);
-        assert_eq!(count_bytes("こんにちは"), 15);
+    fn test_tabulate() {
+        let results = [
+            CountResult { lines: Some(1), words: Some(22), ..Default::default() },
+            CountResult {
+                lines: Some(333),
+                words: Some(4),
+                filename: Some("a long file name.txt".to_owned()),
+                ..Default::default()
+            },
+        ];
+        let options = FormatOptions::from_cli(&Cli { tabulate: true, header: true, ..Default::default() });
+        let rows = format_all(&results, &options);
+
+        assert_eq!(rows, ["1\t22", "333\t4\ta long file name.txt"]);
+        assert_eq!(rows[1].matches('\t').count(), 2);
+        assert_eq!(format_header(&results, &options).unwrap(), "lines\twords\tfilename");
+        assert!(Cli::try_parse_from(["ccwc", "--tabulate", "--column-separator", ","]).is_err());
     }
 
     #[test]
-    fn test_count_lines() {
-        assert_eq!(count_lines(""), 0);
-        assert_eq!(count_lines("Hello\nworld"), 2);
-        assert_eq!(count_lines("Line 1\nLine 2\nLine 3"), 3);
+    fn test_file_not_found_error() {
+        let temp = temp_dir("file-not-found");
+        let missing = write_temp_file(&temp, "a.txt", "") + ".missing";
+        let cli = Cli { filenames: vec![missing.clone()], ..Default::default() };
+
+        match run_structured(cli) {
+            Err(CcwcError::FileNotFound(filename)) => assert_eq!(filename, missing),
+            other => panic!("expected FileNotFound, got {other:?}"),
+        }
     }
 
     #[test]
-    fn test_count_words() {
-        assert_eq!(count_words(""), 0);
-        assert_eq!(count_words("Hello,\nworld!"), 2);
-        assert_eq!(count_words("This is a sentence."), 4);
+    fn test_invalid_utf8_error() {
+        let temp = temp_dir("invalid-utf8");
+        let path = write_temp_file(&temp, "a.txt", "");
+        fs::write(&path, b"hello \xff world").unwrap();
+        let cli = Cli { filenames: vec![path], ..Default::default() };
+
+        let error = run_structured(cli).unwrap_err();
+        assert!(matches!(error, CcwcError::InvalidUtf8(_)));
+        assert!(error.source().is_some());
     }
 }
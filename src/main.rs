@@ -1,20 +1,74 @@
-/// A wc clone built in Rust.
+//! A wc clone built in Rust.
 
-use std::process;
+use std::{io, process};
 
-use ccwc::{run, Cli};
-use clap::Parser;
+#[cfg(feature = "watch")]
+use ccwc::watch;
+use ccwc::{check, output_target, run_quiet, run_to, CcwcError, Cli, CountConfig};
+use clap::{CommandFactory, Parser};
 
 fn main() {
     let cli = Cli::parse();
 
-    println!("{cli:?}");
+    if let Some(shell) = cli.completions_shell() {
+        clap_complete::generate(shell, &mut Cli::command(), "ccwc", &mut io::stdout());
+        return;
+    }
 
-    match run(cli) {
-        Ok(result) => println!("{}", result),
-        Err(e) => {
-            eprintln!("Application error: {e}");
-            process::exit(1);
+    if cli.print_config_mode() {
+        eprintln!("{:#?}", CountConfig::from_cli(&cli));
+        return;
+    }
+
+    if cli.check_mode() {
+        match check(&cli) {
+            Ok(outcomes) => {
+                for outcome in &outcomes {
+                    if let Some(error) = &outcome.error {
+                        eprintln!("ccwc: {error}");
+                    }
+                    println!("{outcome}");
+                }
+                let failed = outcomes.iter().filter(|outcome| !outcome.matches).count();
+                if failed > 0 {
+                    eprintln!("WARNING: {failed} of {} counts did NOT match", outcomes.len());
+                    process::exit(1);
+                }
+            }
+            Err(e) => exit_with_error(e),
+        }
+        return;
+    }
+
+    if cli.quiet_mode() {
+        match run_quiet(cli) {
+            Ok(true) => return,
+            Ok(false) => process::exit(1),
+            // As with `grep -q`, an error has its own status so that it is not
+            // mistaken for a count of zero
+            Err(e) => {
+                eprintln!("Application error: {e}");
+                process::exit(2);
+            }
+        }
+    }
+
+    #[cfg(feature = "watch")]
+    if cli.watch_mode() {
+        if let Err(e) = watch(cli) {
+            exit_with_error(e);
         }
-    };
+        return;
+    }
+
+    let result = output_target(&cli).and_then(|mut output| run_to(cli, &mut output));
+    if let Err(e) = result {
+        exit_with_error(e);
+    }
+}
+
+/// Prints the error to stderr and exits with a non-zero exit code
+fn exit_with_error(e: CcwcError) -> ! {
+    eprintln!("Application error: {e}");
+    process::exit(1);
 }
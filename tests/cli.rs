@@ -0,0 +1,380 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+mod common;
+use common::write_input;
+
+/// Runs the ccwc binary with the given arguments
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ccwc"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Runs the ccwc binary with the given arguments, writing the input to its
+/// stdin
+fn run_with_stdin(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ccwc"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_long_flags_match_short_flags() {
+    let path = write_input("long-flags", "input.txt", "hello, world\nこんにちは\n");
+    let path = path.to_str().unwrap();
+
+    for (short, long) in [("-c", "--bytes"), ("-w", "--words"), ("-l", "--lines"), ("-m", "--chars")] {
+        let short_output = run(&[short, path]);
+        let long_output = run(&[long, path]);
+
+        assert!(short_output.status.success());
+        assert!(long_output.status.success());
+        assert_eq!(short_output.stdout, long_output.stdout, "{short} and {long} differ");
+    }
+
+    let short_output = run(&["-c", "-w", "-l", path]);
+    let long_output = run(&["--bytes", "--words", "--lines", path]);
+    assert_eq!(short_output.stdout, long_output.stdout);
+}
+
+#[test]
+fn test_long_flags_in_help() {
+    let output = run(&["--help"]);
+    let help = String::from_utf8(output.stdout).unwrap();
+
+    assert!(help.contains("-c, --bytes"));
+    assert!(help.contains("-w, --words"));
+    assert!(help.contains("-l, --lines"));
+    assert!(help.contains("-m, --chars"));
+}
+
+#[test]
+fn test_no_debug_output() {
+    let path = write_input("no-debug-output", "input.txt", "hello, world\n");
+    let path = path.to_str().unwrap();
+
+    let output = run(&["-l", path]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(!stdout.contains("Cli {"));
+    assert_eq!(stdout, format!("   1 {path}\n"));
+}
+
+#[test]
+fn test_exit_codes() {
+    let path = write_input("exit-codes", "input.txt", "hello, world\n");
+
+    let output = run(&[path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+
+    let missing = path.with_file_name("missing.txt");
+    let output = run(&[missing.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_from_stdin_filenames() {
+    let a = write_input("stdin-filenames", "a.txt", "one\n");
+    let b = write_input("stdin-filenames", "b.txt", "two three\n");
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    let output = run_with_stdin(&["-w", "--from-stdin-filenames"], &format!("{a}\n\n{b}\n"));
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("   1 {a}\n   2 {b}\n   3 total\n")
+    );
+    assert!(!run(&["--from-stdin-filenames", a]).status.success());
+}
+
+#[test]
+fn test_version_includes_build_metadata() {
+    let output = run(&["--version"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.starts_with(&format!("ccwc {} (commit ", env!("CARGO_PKG_VERSION"))));
+    assert!(stdout.contains(", built "));
+}
+
+#[test]
+fn test_generate_completions() {
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        let output = run(&["--generate-completions", shell]);
+
+        assert!(output.status.success());
+        assert!(!output.stdout.is_empty(), "no completions for {shell}");
+    }
+}
+
+#[test]
+fn test_from_nul_stdin() {
+    let a = write_input("nul-stdin", "a.txt", "one\n");
+    let b = write_input("nul-stdin", "b.txt", "two three\n");
+    let input = format!("{}\0{}\0", a.to_str().unwrap(), b.to_str().unwrap());
+
+    let output = run_with_stdin(&["-w", "--from-nul-stdin"], &input);
+    let files0_output = run_with_stdin(&["-w", "--files0-from", "-"], &input);
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout, files0_output.stdout);
+    assert!(String::from_utf8(output.stdout).unwrap().ends_with("   3 total\n"));
+}
+
+#[test]
+fn test_check() {
+    let path = write_input("check", "input.txt", "hello, world\n");
+    let saved = run(&["-lw", path.to_str().unwrap()]);
+    let counts = path.with_file_name("counts.txt");
+    fs::write(&counts, &saved.stdout).unwrap();
+
+    let output = run(&["-lw", "--check", counts.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{}: OK\n", path.display()));
+
+    fs::write(&path, "hello, world\nagain\n").unwrap();
+    let output = run(&["-lw", "--check", counts.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{}: FAILED\n", path.display()));
+
+    // A missing file fails without stopping the other files being checked
+    let missing = path.with_file_name("missing.txt");
+    let mut missing_counts = format!("   1   2 {}\n", missing.display()).into_bytes();
+    missing_counts.extend(&saved.stdout);
+    fs::write(&counts, missing_counts).unwrap();
+    fs::write(&path, "hello, world\n").unwrap();
+    let output = run(&["-lw", "--check", counts.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{}: FAILED open or read\n{}: OK\n", missing.display(), path.display())
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("No such file or directory"), "unexpected error: {stderr}");
+    assert!(stderr.contains("1 of 2 counts did NOT match"), "unexpected error: {stderr}");
+}
+
+#[test]
+fn test_header() {
+    let a = write_input("header", "a.txt", "one\n");
+    let b = write_input("header", "b.txt", "two three\n");
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    let output = run(&["-lw", "--header", a, b]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+
+    assert!(output.status.success());
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "   lines   words filename");
+    assert_eq!(lines[1], format!("       1       1 {a}"));
+
+    let output = run(&["-lw", "--header", "--omit-header", a, b]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 3);
+}
+
+#[test]
+fn test_stdin_label() {
+    let output = run_with_stdin(&["-w", "--stdin-label", "<stdin>"], "one two\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "   2 <stdin>\n");
+
+    let output = run_with_stdin(&["-w", "--posix"], "one two\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "   2 -\n");
+}
+
+#[test]
+fn test_output_file() {
+    let path = write_input("output-file", "input.txt", "one two\n");
+    let output_path = path.with_file_name("output.txt");
+    let (path, output_path) = (path.to_str().unwrap(), output_path.to_str().unwrap());
+    fs::write(output_path, "old contents\n").unwrap();
+
+    let output = run(&["-w", "--output-file", output_path, path]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(fs::read_to_string(output_path).unwrap(), format!("   2 {path}\n"));
+
+    let output = run(&["-l", "--output-append", output_path, path]);
+    assert!(output.status.success());
+    assert_eq!(fs::read_to_string(output_path).unwrap(), format!("   2 {path}\n   1 {path}\n"));
+
+    assert!(!run(&["--output-file", output_path, "--output-append", output_path, path]).status.success());
+}
+
+#[test]
+fn test_zero_terminated() {
+    let a = write_input("zero-terminated", "a.txt", "one\n");
+    let b = write_input("zero-terminated", "b.txt", "two three\n");
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    let output = run(&["-w", "-Z", a, b]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout.matches('\0').count(), 3);
+    assert!(!stdout.contains('\n'));
+    assert_eq!(stdout, format!("   1 {a}\0   2 {b}\0   3 total\0"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_print0_with_xargs() {
+    let a = write_input("print0", "a.txt", "one\n");
+    let b = write_input("print0", "b.txt", "two three\n");
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    let output = run(&["-w", "--print0", a, b]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, run(&["-w", "-Z", a, b]).stdout);
+
+    // Each record, including the last, becomes a separate argument to echo
+    let mut xargs = Command::new("xargs")
+        .args(["-0", "echo"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    xargs.stdin.take().unwrap().write_all(&output.stdout).unwrap();
+    let echoed = xargs.wait_with_output().unwrap();
+
+    assert!(echoed.status.success());
+    assert_eq!(String::from_utf8(echoed.stdout).unwrap(), format!("   1 {a}    2 {b}    3 total\n"));
+}
+
+#[test]
+fn test_number() {
+    let a = write_input("number", "a.txt", "one\n");
+    let b = write_input("number", "b.txt", "two three\n");
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    let output = run(&["--number", "-l", a]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1\n");
+
+    let output = run(&["--count-only", a, b]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1 1 4\n1 2 10\n2 3 14\n");
+
+    let output = run_with_stdin(&["--number", "-wc"], "one two\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "2 8\n");
+}
+
+#[test]
+fn test_histogram() {
+    let a = write_input("histogram", "a.txt", "one\ntwo\n");
+    let b = write_input("histogram", "b.txt", "a line that is longer than ten\n");
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    let output = run(&["-l", "--histogram", "--histogram-buckets", "3", a, b]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    let (full, half) = ("#".repeat(40), "#".repeat(20));
+    assert_eq!(
+        stdout,
+        format!("   2 {a}\n   1 {b}\n   3 total\n\n  0-9 | {full} 2\n10-19 |  0\n  20+ | {half} 1\n")
+    );
+    assert!(!run(&["--histogram", "--histogram-buckets", "0", a]).status.success());
+
+    // Inputs left out by the limits are still in the histogram
+    let output = run(&["-l", "--histogram", "--histogram-buckets", "2", "--min-lines", "5", a]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("0-9 | {full} 2\n10+ |  0\n"));
+}
+
+#[test]
+fn test_quiet() {
+    let path = write_input("quiet", "input.txt", "one two\n");
+    let empty = write_input("quiet", "empty.txt", "");
+    let (path, empty) = (path.to_str().unwrap(), empty.to_str().unwrap());
+
+    let output = run(&["-q", "-l", path]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    let output = run(&["--quiet", "-l", empty]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+
+    let output = run(&["-q", path]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("exactly one count"));
+
+    let output = run(&["-q", "-l", "missing.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    assert!(!output.stderr.is_empty());
+}
+
+#[test]
+fn test_assert_max() {
+    let path = write_input("assert-max", "input.txt", "one\ntwo\nthree\n");
+    let path = path.to_str().unwrap();
+
+    let output = run(&["-l", "--assert-max", "3", path]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("   3 {path}\n"));
+
+    let output = run(&["-l", "--assert-max", "2", path]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("more than the maximum of 2"));
+}
+
+#[test]
+fn test_print_config() {
+    let output = run(&["--print-config", "-c", "-m", "missing.txt"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("CountConfig {"), "unexpected config: {stderr}");
+    assert!(stderr.contains("count_chars: Chars,"), "unexpected config: {stderr}");
+
+    let output = run(&["--dry-run", "-l"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("count_lines: true,"));
+}
+
+#[test]
+fn test_encoding_detect() {
+    let path = write_input("encoding-detect", "latin1.txt", "");
+    fs::write(&path, b"caf\xE9 na\xEFve, d\xE9j\xE0 vu\n").unwrap();
+    let path = path.to_str().unwrap();
+
+    let output = run(&["-m", "--encoding-detect", path]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("  20 {path}\n"));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!("ccwc: {path}: detected encoding windows-1252\n")
+    );
+
+    let output = run(&["-c", "--crlf", "--encoding-detect", path]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("  20 {path}\n"));
+
+    let output = run(&["--encoding-detect", "--encoding", "latin-1", path]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_skip_binary() {
+    let path = write_input("skip-binary", "a.bin", "\0\x01\x02 three\n");
+    let path = path.to_str().unwrap();
+
+    let output = run(&["-w", "--skip-binary", path]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.iter().all(u8::is_ascii_whitespace));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!("ccwc: {path}: skipping binary file\n")
+    );
+}
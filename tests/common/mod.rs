@@ -0,0 +1,37 @@
+//! Helpers shared by the integration tests
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use tempfile::TempDir;
+
+/// A file written by `write_input` in its own temporary directory, which is
+/// removed along with anything else written to it when this is dropped
+pub struct TempInput {
+    path: PathBuf,
+    _dir: TempDir,
+}
+
+impl Deref for TempInput {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TempInput {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Writes the given contents to a file in a fresh temporary directory for the
+/// named test and returns the file, which is deleted when it is dropped
+pub fn write_input(test_name: &str, filename: &str, contents: &str) -> TempInput {
+    let dir = tempfile::Builder::new().prefix(&format!("ccwc-{test_name}-")).tempdir().unwrap();
+    let path = dir.path().join(filename);
+    fs::write(&path, contents).unwrap();
+    TempInput { path, _dir: dir }
+}
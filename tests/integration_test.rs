@@ -0,0 +1,65 @@
+//! Runs the ccwc binary on fixed inputs and checks its output. The expected
+//! counts are those printed by GNU `wc` in a UTF-8 locale for the same
+//! inputs, laid out in ccwc's columns.
+
+use assert_cmd::Command;
+
+mod common;
+use common::write_input;
+
+/// Contains ASCII, multi-byte characters, and a tab, and is 3 lines, 6 words,
+/// 50 bytes, and 36 characters long according to GNU `wc`
+const INPUT: &str = "hello, world\nこんにちは 世界\n\ttabbed  line\n";
+
+/// A Command that runs the ccwc binary
+fn ccwc() -> Command {
+    Command::cargo_bin("ccwc").unwrap()
+}
+
+#[test]
+fn test_single_flags() {
+    let path = write_input("single-flags", "input.txt", INPUT);
+    let path = path.to_str().unwrap();
+
+    for (flag, expected) in [("-c", "  50"), ("-w", "   6"), ("-l", "   3"), ("-m", "  36")] {
+        ccwc()
+            .args([flag, path])
+            .assert()
+            .success()
+            .stdout(format!("{expected} {path}\n"));
+    }
+}
+
+#[test]
+fn test_all_flags() {
+    let path = write_input("all-flags", "input.txt", INPUT);
+    let path = path.to_str().unwrap();
+
+    ccwc().arg(path).assert().success().stdout(format!("   3   6  50 {path}\n"));
+    ccwc().args(["-l", "-w", "-c", path]).assert().success().stdout(format!("   3   6  50 {path}\n"));
+    ccwc().args(["-lwm", path]).assert().success().stdout(format!("   3   6  36 {path}\n"));
+}
+
+#[test]
+fn test_stdin() {
+    ccwc().write_stdin(INPUT).assert().success().stdout("   3   6  50\n");
+    ccwc().arg("-m").write_stdin(INPUT).assert().success().stdout("  36\n");
+}
+
+#[test]
+fn test_missing_file() {
+    let path = write_input("missing-file", "input.txt", INPUT).with_file_name("missing.txt");
+
+    let assert = ccwc().arg(&path).assert().failure().code(1).stdout("");
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(stderr.contains("No such file or directory"), "unexpected error: {stderr}");
+}
+
+#[test]
+fn test_empty_input() {
+    let path = write_input("empty-input", "input.txt", "");
+    let path = path.to_str().unwrap();
+
+    ccwc().write_stdin("").assert().success().stdout("   0   0   0\n");
+    ccwc().args(["-m", path]).assert().success().stdout(format!("   0 {path}\n"));
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "json")]
+
+use std::process::Command;
+
+use serde_json::Value;
+
+mod common;
+use common::write_input;
+
+/// Runs the ccwc binary with the given arguments and parses the JSON it prints
+fn run_json(args: &[&str]) -> Value {
+    let output = Command::new(env!("CARGO_BIN_EXE_ccwc"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    serde_json::from_str(&stdout).unwrap()
+}
+
+#[test]
+fn test_json_single_file() {
+    let path = write_input("json-single", "a.txt", "hello, world\nsecond line\n");
+    let path = path.to_str().unwrap();
+
+    let json = run_json(&["--json", path]);
+
+    assert_eq!(json["filename"], path);
+    assert_eq!(json["lines"], 2);
+    assert_eq!(json["words"], 4);
+    assert_eq!(json["bytes"], 25);
+    assert_eq!(json["chars"], Value::Null);
+    assert_eq!(json["max_line_length"], Value::Null);
+}
+
+#[test]
+fn test_json_multiple_files() {
+    let a = write_input("json-multiple", "a.txt", "one\n");
+    let b = write_input("json-multiple", "b.txt", "two three\n");
+
+    let json = run_json(&["--json", "-m", a.to_str().unwrap(), b.to_str().unwrap()]);
+    let results = json.as_array().unwrap();
+
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0]["chars"], 4);
+    assert_eq!(results[1]["chars"], 10);
+    assert_eq!(results[2]["filename"], "total");
+    assert_eq!(results[2]["chars"], 14);
+    assert_eq!(results[2]["bytes"], Value::Null);
+    assert_eq!(results[2]["lines"], Value::Null);
+}
+
+#[test]
+fn test_json_input_format() {
+    let path = write_input("json-input", "doc.json", r#"{"title": "Hello there", "body": ["one", 2]}"#);
+    let path = path.to_str().unwrap();
+
+    let json = run_json(&["--json", "--input-format", "json", "-lw", path]);
+
+    assert_eq!(json["lines"], 2);
+    assert_eq!(json["words"], 3);
+}